@@ -9,7 +9,10 @@ use std::error::Error;
 #[derive(Debug)]
 pub enum RuntimeError {
     Return(Literal),  // 处理return语句
+    Break,            // 处理break语句，由所在循环捕获
+    Continue,         // 处理continue语句，由所在循环捕获
     Runtime(String),  // (错误token, 错误信息)
+    UserThrow(Literal), // throw 语句抛出的值，与解释器内部的运行时错误区分
 }
 
 // 实现 Display 提供错误描述
@@ -17,6 +20,8 @@ impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             RuntimeError::Return(_) => write!(f, "Return statement correctly."),
+            RuntimeError::Break => write!(f, "Break statement correctly."),
+            RuntimeError::Continue => write!(f, "Continue statement correctly."),
             RuntimeError::Runtime(msg) => {
                 // 特判以 "Error: " 开头的消息
                 if msg.starts_with("Error: ") {
@@ -26,6 +31,7 @@ impl fmt::Display for RuntimeError {
                     write!(f, "RuntimeError: {}", msg)
                 }
             }
+            RuntimeError::UserThrow(value) => write!(f, "RuntimeError: {}", value),
         }
     }
 }
@@ -37,10 +43,19 @@ impl Error for RuntimeError {
 }
 
 type Result<T> = std::result::Result<T, RuntimeError>;
-// 环境嵌套结构（支持作用域链）
+
+fn undefined_variable(name: &Token) -> RuntimeError {
+    RuntimeError::Runtime(format!("[line {}] Undefined variable '{}'.", name.line, name.lexeme))
+}
+
+// 环境嵌套结构（支持作用域链）。
+// 全局变量、类的方法表和实例字段按名字存放在 values 中；
+// 解析器跟踪的局部变量按解析器分配的槽位存放在 slots 中，读写时直接下标访问
 #[derive(Debug, Clone, Serialize)]
 pub struct Environment {
     pub values: HashMap<String, Literal>,
+    slots: Vec<Option<Literal>>, // None 表示已预留槽位但尚未定义（如块内提前声明的函数名）
+    slot_names: Vec<String>,     // 各槽位的变量名，供调试器按名字查找和列出变量
     pub enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
@@ -48,6 +63,8 @@ impl Environment {
     pub fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Rc<RefCell<Self>> {
         Rc::new(RefCell::new(Self {
             values: HashMap::new(),
+            slots: Vec::new(),
+            slot_names: Vec::new(),
             enclosing,
         }))
     }
@@ -56,9 +73,49 @@ impl Environment {
         self.values.insert(name, value);
     }
 
+    /// 定义局部变量。槽位按首次定义的顺序分配，与解析器为该作用域分配的顺序一致；
+    /// 同名变量再次定义时覆盖原槽位
+    pub fn define_local(&mut self, name: &str, value: Literal) {
+        let slot = self.local_slot(name);
+        self.slots[slot] = Some(value);
+    }
+
+    /// 为块内提前声明的函数名预留槽位，执行到函数声明时才真正定义
+    pub fn reserve_local(&mut self, name: &str) {
+        self.local_slot(name);
+    }
+
+    fn local_slot(&mut self, name: &str) -> usize {
+        match self.slot_names.iter().position(|n| n == name) {
+            Some(slot) => slot,
+            None => {
+                self.slot_names.push(name.to_string());
+                self.slots.push(None);
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    // 按名字查找本层的绑定，先查 values 再查局部变量槽位
+    fn lookup(&self, name: &str) -> Option<&Literal> {
+        self.values.get(name).or_else(|| {
+            let slot = self.slot_names.iter().position(|n| n == name)?;
+            self.slots[slot].as_ref()
+        })
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Literal> {
+        if self.values.contains_key(name) {
+            return self.values.get_mut(name);
+        }
+        let slot = self.slot_names.iter().position(|n| n == name)?;
+        self.slots[slot].as_mut()
+    }
+
+    /// 按名字沿作用域链查找，用于全局变量和调试器中未经解析的代码
     pub fn get(&self, name: &Token) -> Result<Literal> {
         let key = &name.lexeme;
-        if let Some(val) = self.values.get(key) {
+        if let Some(val) = self.lookup(key) {
             Ok(val.clone())
         } else if let Some(env) = &self.enclosing {
             env.borrow().get(name)
@@ -67,27 +124,55 @@ impl Environment {
             if key == "this" {
                 Err(RuntimeError::Runtime("this isn't bound in environment.".into()))
             } else {
-                Err(RuntimeError::Runtime(format!("Undefined variable '{}'.", key)))
+                Err(undefined_variable(name))
             }
         }
     }
 
     pub fn assign(&mut self, name: &Token, value: Literal) -> Result<()> {
-        let key = &name.lexeme;
-        if self.values.contains_key(key) {
-            self.values.insert(key.to_string(), value);
+        if let Some(slot) = self.lookup_mut(&name.lexeme) {
+            *slot = value;
             Ok(())
         } else if let Some(env) = &mut self.enclosing {
             env.borrow_mut().assign(name, value)
         } else {
-            Err(RuntimeError::Runtime(format!("Undefined variable '{}'.", key)))
+            Err(undefined_variable(name))
+        }
+    }
+
+    /// 沿作用域链恰好向外走 depth 层，读取第 slot 个局部变量；depth 和 slot 由解析器静态计算，
+    /// name 只用于错误信息。槽位尚未定义时（如变量声明前就调用了捕获它的闭包）报未定义变量
+    pub fn get_at(&self, depth: usize, slot: usize, name: &Token) -> Result<Literal> {
+        if depth == 0 {
+            self.slots.get(slot).cloned().flatten().ok_or_else(|| undefined_variable(name))
+        } else if let Some(env) = &self.enclosing {
+            env.borrow().get_at(depth - 1, slot, name)
+        } else {
+            Err(undefined_variable(name))
+        }
+    }
+
+    /// 沿作用域链恰好向外走 depth 层，为第 slot 个局部变量赋值
+    pub fn assign_at(&mut self, depth: usize, slot: usize, name: &Token, value: Literal) -> Result<()> {
+        if depth == 0 {
+            match self.slots.get_mut(slot) {
+                Some(Some(current)) => {
+                    *current = value;
+                    Ok(())
+                }
+                _ => Err(undefined_variable(name)),
+            }
+        } else if let Some(env) = &self.enclosing {
+            env.borrow_mut().assign_at(depth - 1, slot, name, value)
+        } else {
+            Err(undefined_variable(name))
         }
     }
 
     /// 检查当前环境链中是否存在 "this" 绑定
     pub fn has_this(&self) -> bool {
         // 检查当前环境
-        if self.values.contains_key("this") {
+        if self.lookup("this").is_some() {
             return true;
         }
 
@@ -114,27 +199,67 @@ impl Environment {
         }
     }
 
+    /// 按名字顺序遍历本层的绑定（包括局部变量槽位），不包括外层作用域
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Literal)> {
+        let locals = self
+            .slot_names
+            .iter()
+            .zip(&self.slots)
+            .filter_map(|(name, value)| Some((name.as_str(), value.as_ref()?)));
+        let mut bindings: Vec<(&str, &Literal)> = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+            .chain(locals)
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(b.0));
+        bindings.into_iter()
+    }
+
+    /// 从本层开始沿作用域链向外遍历全部绑定，附带所在层的深度（本层为 0），
+    /// 每层内按名字排序。外层环境在 RefCell 中，因此返回克隆的值
+    pub fn iter_chain(&self) -> impl Iterator<Item = (usize, String, Literal)> {
+        let mut bindings: Vec<(usize, String, Literal)> = self
+            .iter()
+            .map(|(name, value)| (0, name.to_string(), value.clone()))
+            .collect();
+        if let Some(enclosing) = &self.enclosing {
+            bindings.extend(
+                enclosing
+                    .borrow()
+                    .iter_chain()
+                    .map(|(depth, name, value)| (depth + 1, name, value)),
+            );
+        }
+        bindings.into_iter()
+    }
+
     pub fn debug_print(&self, depth: usize) {
-        println!("🛠️  Environment Depth {}:", depth);
-        for (key, val) in &self.values {
-            match val {
+        print!("{}", self.debug_format(depth));
+    }
+
+    /// debug_print 的文本内容，变量按名字排序，保证每次输出一致
+    pub fn debug_format(&self, depth: usize) -> String {
+        let mut out = format!("🛠️  Environment Depth {}:\n", depth);
+        for (key, value) in self.iter() {
+            let line = match value {
                 Literal::InstanceValue(inst) => {
-                    println!("   🔑 {} => 🏷️ {} (Instance of {})", 
-                        key, inst.name, inst.class.name);
+                    format!("   🔑 {} => 🏷️ {} (Instance of {})", key, inst.name, inst.class.name)
                 }
-                Literal::ClassValue(cls) => {
-                    println!("   🔑 {} => 🏛️ {}", key, cls.name);
-                }
-                _ => println!("   🔑 {} => {:?}", key, val),
-            }
+                Literal::ClassValue(cls) => format!("   🔑 {} => 🏛️ {}", key, cls.name),
+                val => format!("   🔑 {} => {:?}", key, val),
+            };
+            out.push_str(&line);
+            out.push('\n');
         }
         if let Some(enclosing) = &self.enclosing {
-            enclosing.borrow_mut().debug_print(depth + 1);
+            out.push_str(&enclosing.borrow().debug_format(depth + 1));
         }
+        out
     }
 
     pub fn debug_loc(&self) -> String {
-        if self.values.contains_key("this") {
+        if self.lookup("this").is_some() {
             "[有 this 绑定]".into()
         } else if let Some(env) = &self.enclosing {
             env.borrow().debug_loc()
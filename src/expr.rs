@@ -1,5 +1,15 @@
 use crate::token::{Token, Literal};
+use crate::statement::Stmt;
 use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// 变量引用类表达式的唯一编号，解析器按编号记录作用域深度。
+// AST 会被克隆进函数对象，因此不能用节点地址作为键。
+static NEXT_EXPR_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub fn next_expr_id() -> usize {
+    NEXT_EXPR_ID.fetch_add(1, Ordering::Relaxed)
+}
 
 #[derive(Clone, Debug, Serialize)]
 pub enum Expr {
@@ -8,6 +18,12 @@ pub enum Expr {
         operator: Token,
         right: Box<Expr>,
     },
+    // and / or，需要短路求值，因此与 Binary 分开
+    Logical {
+        left: Box<Expr>,
+        operator: Token,
+        right: Box<Expr>,
+    },
     Unary {
         operator: Token,
         right: Box<Expr>,
@@ -19,18 +35,22 @@ pub enum Expr {
         value: Literal,
     },
     Variable {
+        id: usize,
         name: Token,
     },
     Call {
         callee: Box<Expr>,      // 被调用的函数表达式
         paren: Token,           // 右括号token
         arguments: Vec<Expr>,    // 参数列表
+        spread: Vec<bool>,       // 与 arguments 一一对应，true 表示 ...expr，调用时展开为多个参数
     },
     Super {
+        id: usize,
         keyword: Token,      // super关键字token
         method: Token,       // 要调用的方法名
     },
     Assign {
+        id: usize,
         name: Token,        // 被赋值的目标
         value: Box<Expr>,   // 赋值的表达式
     },
@@ -45,6 +65,49 @@ pub enum Expr {
     },
     // this表达式
     This {
+        id: usize,
         keyword: Token,
     },
+    // 列表字面量 [a, b, c]
+    ListLiteral {
+        elements: Vec<Expr>,
+    },
+    // 字典字面量 {"a": 1}
+    MapLiteral {
+        entries: Vec<(Expr, Expr)>,
+        brace: Token, // 左花括号token，用于报告行号
+    },
+    // 下标访问 object[index]（列表或字典）
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        bracket: Token, // 右方括号token，用于报告行号
+    },
+    // 下标赋值 object[index] = value
+    SetIndex {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        bracket: Token,
+        value: Box<Expr>,
+    },
+    // 属性或下标的复合赋值 obj.x += v / list[i] += v，对象和下标只求值一次
+    // （变量的复合赋值直接脱糖为 Assign）
+    CompoundAssign {
+        target: Box<Expr>, // GetAttribute 或 Index
+        operator: Token,   // 对应的二元运算符，如 += 对应 +
+        value: Box<Expr>,
+    },
+    // 匿名函数表达式 fun (a, b) { ... }
+    Lambda {
+        params: Vec<Token>,
+        has_rest: bool,
+        body: Vec<Stmt>,
+    },
+    // 匿名类表达式
+    ClassExpr {
+        superclass: Option<Box<Expr>>,
+        methods: Vec<Stmt>,
+        statics: Vec<Stmt>,
+        fields: Vec<Stmt>,
+    },
 }
\ No newline at end of file
@@ -1,17 +1,302 @@
 use crate::environment::{Environment, RuntimeError};
 use crate::expr::Expr;
+use crate::native;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::syntaxer::{render_parse_errors, ParseError, Parser};
+use crate::UnifiedError;
 use crate::statement::Stmt;
 use crate::token::*;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::io::{self, Write};
 use std::rc::Rc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant};
 
 type Result<T> = std::result::Result<T, RuntimeError>;
 
+// 字符串按字典序比较：Ordering 映射为 -1/0/1，再与 0 比较即可复用数值比较闭包
+fn comp_str(a: &str, b: &str) -> f64 {
+    a.cmp(b) as i8 as f64
+}
+
+fn output_error(e: io::Error) -> RuntimeError {
+    RuntimeError::Runtime(format!("Failed to write output: {}.", e))
+}
+
+// 与调用方共享的内存缓冲区，用于捕获 print 输出
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 值的显示形式，print、字符串拼接和 str() 共用，与 Literal 的 Display 相同
+pub fn stringify(value: &Literal) -> String {
+    value.to_string()
+}
+
+// 下标必须是落在 [0, len) 内的整数；for 循环计数器这类整数值的 f64 可以直接使用。
+// kind 为 "List" 或 "String"，用于错误信息
+fn sequence_index(index: &Literal, len: usize, kind: &str, bracket: &Token) -> Result<usize> {
+    match index {
+        Literal::NumberValue(n) if n.fract() != 0.0 || n.is_nan() => Err(error_at(
+            bracket,
+            &format!("{} index must be an integer but got {}.", kind, format_number(*n)),
+        )),
+        Literal::NumberValue(n) if *n < 0.0 || *n >= len as f64 => Err(error_at(
+            bracket,
+            &format!("{} index {} out of range for length {}.", kind, format_number(*n), len),
+        )),
+        Literal::NumberValue(n) => Ok(*n as usize),
+        other => Err(error_at(
+            bracket,
+            &format!("{} index must be a number but got {}.", kind, other.type_name()),
+        )),
+    }
+}
+
+// 位运算按 64 位二进制补码进行：操作数必须是 [-2^63, 2^63) 内的整数，
+// 超出范围报错而不是让 as i64 静默饱和。结果转回 f64，绝对值超过 2^53 时可能丢失精度
+fn bitwise_operand(value: &Literal, operator: &Token) -> Result<i64> {
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0; // 2^63
+    let n = match value {
+        Literal::NumberValue(n) => *n,
+        other => {
+            return Err(error_at(
+                operator,
+                &format!("Operand of '{}' must be a number but got {}.", operator.lexeme, other.type_name()),
+            ));
+        }
+    };
+    if n.fract() != 0.0 || n.is_nan() {
+        return Err(error_at(
+            operator,
+            &format!("Operand of '{}' must be an integer but got {}.", operator.lexeme, format_number(n)),
+        ));
+    }
+    if !(-LIMIT..LIMIT).contains(&n) {
+        return Err(error_at(operator, "Operand too large for bitwise operation."));
+    }
+    Ok(n as i64)
+}
+
+// 移位数必须在 0..64 内；左移溢出的高位被丢弃，右移为算术右移
+fn bitwise(left: &Literal, right: &Literal, operator: &Token) -> Result<Literal> {
+    let a = bitwise_operand(left, operator)?;
+    let b = bitwise_operand(right, operator)?;
+    let result = match operator.token_type {
+        TokenType::Ampersand => a & b,
+        TokenType::Pipe => a | b,
+        TokenType::Caret => a ^ b,
+        TokenType::LessLess | TokenType::GreaterGreater => {
+            if !(0..64).contains(&b) {
+                return Err(error_at(
+                    operator,
+                    &format!("Shift amount must be between 0 and 63 but got {}.", b),
+                ));
+            }
+            if operator.token_type == TokenType::LessLess {
+                a << b
+            } else {
+                a >> b
+            }
+        }
+        _ => unreachable!(),
+    };
+    Ok(Literal::NumberValue(result as f64))
+}
+
+// 带行号的运行时错误，行号取自出错的运算符或括号等token
+fn error_at(token: &Token, message: &str) -> RuntimeError {
+    RuntimeError::Runtime(format!("[line {}] {}", token.line, message))
+}
+
+// 去掉 error_at 加在错误信息开头的 "[line N] "
+fn strip_line(message: &str) -> &str {
+    message
+        .strip_prefix("[line ")
+        .and_then(|rest| rest.split_once("] "))
+        .filter(|(line, _)| line.bytes().all(|b| b.is_ascii_digit()))
+        .map_or(message, |(_, rest)| rest)
+}
+
+// 注册原生函数，纯函数不需要访问解释器
+fn define_native(
+    env: &Rc<RefCell<Environment>>,
+    name: &str,
+    arity: Option<usize>,
+    func: fn(&[Literal]) -> Result<Literal>,
+) {
+    let native = NativeFunction::new(name, arity, move |_, args| func(args));
+    env.borrow_mut().define(name.to_string(), Literal::NativeFunctionValue(Rc::new(native)));
+}
+
+// 注册需要访问解释器状态（输出、随机数、回调 Lox 函数等）的原生函数
+fn define_interpreter_native(
+    env: &Rc<RefCell<Environment>>,
+    name: &str,
+    arity: Option<usize>,
+    func: fn(&mut Interpreter, &[Literal]) -> Result<Literal>,
+) {
+    let native = NativeFunction::new(name, arity, func);
+    env.borrow_mut().define(name.to_string(), Literal::NativeFunctionValue(Rc::new(native)));
+}
+
+// 有 ...rest 参数的函数至少需要 rest 之前的参数个数，否则实参个数必须与形参个数一致
+fn check_function_arity(func: &LoxFunction, got: usize, paren: &Token) -> Result<()> {
+    if !func.has_rest {
+        return check_arity(func.params.len(), got, paren);
+    }
+    let required = func.params.len() - 1;
+    if got < required {
+        return Err(error_at(
+            paren,
+            &format!("Expected at least {} arguments but got {}.", required, got),
+        ));
+    }
+    Ok(())
+}
+
+// 实参个数必须与形参个数（或原生函数声明的 arity）一致
+fn check_arity(expected: usize, got: usize, paren: &Token) -> Result<()> {
+    if expected != got {
+        return Err(error_at(
+            paren,
+            &format!("Expected {} arguments but got {}.", expected, got),
+        ));
+    }
+    Ok(())
+}
+
+// 下标读取：列表、字符串和字典
+fn get_index(object: &Literal, index: &Literal, bracket: &Token) -> Result<Literal> {
+    match object {
+        Literal::ListValue(list) => {
+            let list = list.borrow();
+            let i = sequence_index(index, list.len(), "List", bracket)?;
+            Ok(list[i].clone())
+        }
+        // 字符串按 Unicode 标量值（char）计数，返回单个字符组成的字符串
+        Literal::StringValue(s) => {
+            let i = sequence_index(index, s.chars().count(), "String", bracket)?;
+            Ok(Literal::StringValue(s.chars().nth(i).unwrap().to_string().into()))
+        }
+        // 字典中不存在的键返回 nil
+        Literal::MapValue(map) => {
+            let key = map_key(index, bracket)?;
+            Ok(map.borrow().get(key).cloned().unwrap_or(Literal::Nil))
+        }
+        other => Err(not_indexable(other, bracket)),
+    }
+}
+
+// 下标赋值：列表按下标替换，字典插入或覆盖，字符串不可修改
+fn set_index(object: &Literal, index: &Literal, bracket: &Token, val: Literal) -> Result<()> {
+    match object {
+        Literal::ListValue(list) => {
+            let mut list = list.borrow_mut();
+            let i = sequence_index(index, list.len(), "List", bracket)?;
+            list[i] = val;
+            Ok(())
+        }
+        Literal::StringValue(_) => Err(error_at(bracket, "Strings are immutable.")),
+        // 新键插入，已有的键覆盖
+        Literal::MapValue(map) => {
+            let key = map_key(index, bracket)?;
+            map.borrow_mut().insert(key.to_string(), val);
+            Ok(())
+        }
+        other => Err(not_indexable(other, bracket)),
+    }
+}
+
+// 字典的键必须是字符串
+fn map_key<'a>(key: &'a Literal, bracket: &Token) -> Result<&'a str> {
+    key.as_string().ok_or_else(|| {
+        error_at(
+            bracket,
+            &format!("Map key must be a string but got {}.", key.type_name()),
+        )
+    })
+}
+
+fn not_indexable(value: &Literal, bracket: &Token) -> RuntimeError {
+    error_at(
+        bracket,
+        &format!("Only lists, strings and maps can be indexed but got {}.", value.type_name()),
+    )
+}
+
+/// 比较运算（< <= > >=）的类型规则
+///
+/// - `Strict`（默认）：两侧必须同为数字或同为字符串。
+/// - `Loose`：数字与字符串比较时，先把字符串（去掉首尾空白）解析为数字再比较，
+///   解析失败则报运行时错误；其余类型组合与 `Strict` 相同。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ComparisonMode {
+    #[default]
+    Strict,
+    Loose,
+}
+
 pub struct Interpreter {
     environment: Rc<RefCell<Environment>>,
+    globals: Rc<RefCell<Environment>>,
+    locals: HashMap<usize, (usize, usize)>, // 解析器计算的局部变量 (深度, 槽位)，键为表达式编号
     instance_counter: usize, // 新增实例计数器
+    comparison_mode: ComparisonMode,
+    string_plus_coerces: bool, // 字符串与数字/布尔相加时是否自动转为字符串
+    max_string_length: Option<usize>, // 拼接产生的字符串最大字节数，None 表示不限制
+    output: Box<dyn Write>, // print 语句的输出目标，默认为标准输出
+    interactive_debug: bool, // breakpoint() 是否进入调试 REPL
+    in_debugger: bool, // 调试 REPL 中的输入未经解析，变量按名字沿当前作用域链查找
+    repl_mode: bool, // REPL 中顶层的表达式语句回显其值
+    deterministic: bool, // 确定性模式：sleep() 等依赖真实时间的内置函数不产生等待
+    profile: Option<HashMap<String, ProfileEntry>>, // 开启性能分析时按函数名统计，None 表示关闭
+    call_depth: usize, // 当前正在执行的函数调用层数
+    max_call_depth: usize, // 调用层数上限，超过时报 Stack overflow.
+    rng_state: u64, // random() 等使用的 xorshift64* 状态，不能为 0
+    native_call_line: usize, // 正在执行的原生函数所在的行，原生函数回调出错时报告该行
+}
+
+/// 默认的函数调用层数上限。调试构建下每层 Lox 调用约占 6~11KB 宿主栈（发布构建约 2~4KB），
+/// 1000 层在调试构建下约需 11MB，发布构建下约需 4MB
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+// 单个函数的性能统计
+#[derive(Default)]
+struct ProfileEntry {
+    calls: usize,
+    total: Duration,
+    active: usize, // 正在执行的调用层数，递归调用只在最外层计时，避免重复累计
+}
+
+// 未指定种子时的初始种子：有系统时间时取当前纳秒数，no-std 下使用固定值
+#[cfg(not(feature = "no-std"))]
+fn initial_seed() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_nanos() as u64)
+}
+
+#[cfg(feature = "no-std")]
+fn initial_seed() -> u64 {
+    0
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
@@ -28,234 +313,589 @@ impl Interpreter {
         exprs.iter().map(|expr| self.evaluate(expr)).collect()
     }
 
+    // 按顺序求值实参，...expr 的列表元素依次展开为多个实参；之后才检查参数个数
+    fn evaluate_call_args(&mut self, exprs: &[Expr], spread: &[bool], paren: &Token) -> Result<Vec<Literal>> {
+        let mut args = Vec::with_capacity(exprs.len());
+        for (expr, &is_spread) in exprs.iter().zip(spread) {
+            let value = self.evaluate(expr)?;
+            if !is_spread {
+                args.push(value);
+                continue;
+            }
+            match value {
+                Literal::ListValue(list) => args.extend(list.borrow().iter().cloned()),
+                other => {
+                    return Err(error_at(
+                        paren,
+                        &format!("Can only spread a list but got {}.", other.type_name()),
+                    ));
+                }
+            }
+        }
+        Ok(args)
+    }
+
     pub fn new() -> Self {
         // 预定义全局函数（如clock）
         let env = Environment::new(None);
-        // 定义 clock 函数（返回自 Unix 纪元以来的秒数）
-        env.borrow_mut().define("clock".to_string(), Literal::NativeFunctionValue(|args| {
-            // 参数检查
-            if !args.is_empty() {
-                return Err(RuntimeError::Runtime(
-                    format!("Expected 0 arguments but got {}.", args.len()),
-                ));
-            }
-            
-            // 计算当前时间
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .map_err(|_| RuntimeError::Runtime(
-                    "SystemTime error.".to_string(),
-                ))?;
-            Ok(Literal::NumberValue(now.as_secs_f64()))
-        }));
+        define_native(&env, "contains", Some(2), native::contains);
+        define_native(&env, "starts_with", Some(2), native::starts_with);
+        define_native(&env, "ends_with", Some(2), native::ends_with);
+        define_native(&env, "trim", Some(1), native::trim);
+        define_native(&env, "trim_start", Some(1), native::trim_start);
+        define_native(&env, "trim_end", Some(1), native::trim_end);
+        define_native(&env, "is_number", Some(1), native::is_number);
+        define_native(&env, "is_string", Some(1), native::is_string);
+        define_native(&env, "is_bool", Some(1), native::is_bool);
+        define_native(&env, "is_nil", Some(1), native::is_nil);
+        define_native(&env, "is_function", Some(1), native::is_function);
+        define_native(&env, "is_class", Some(1), native::is_class);
+        define_native(&env, "is_instance", Some(1), native::is_instance);
+        define_native(&env, "is_list", Some(1), native::is_list);
+        define_native(&env, "is_map", Some(1), native::is_map);
+        define_native(&env, "len", Some(1), native::len);
+        define_native(&env, "push", Some(2), native::push);
+        define_native(&env, "pop", Some(1), native::pop);
+        define_native(&env, "substring", Some(3), native::substring);
+        define_native(&env, "indexOf", Some(2), native::index_of);
+        define_native(&env, "charAt", Some(2), native::char_at);
+        define_native(&env, "enumerate", Some(1), native::enumerate);
+        define_interpreter_native(&env, "map", Some(2), native::map);
+        define_interpreter_native(&env, "filter", Some(2), native::filter);
+        define_interpreter_native(&env, "find", Some(2), native::find);
+        define_interpreter_native(&env, "any", Some(2), native::any);
+        define_interpreter_native(&env, "all", Some(2), native::all);
+        define_native(&env, "to_chars", Some(1), native::to_chars);
+        define_native(&env, "from_chars", Some(1), native::from_chars);
+        define_native(&env, "keys", Some(1), native::keys);
+        define_native(&env, "has", Some(2), native::has);
+        define_native(&env, "num", Some(1), native::num);
+        define_native(&env, "str", Some(1), native::str);
+        define_native(&env, "sqrt", Some(1), native::sqrt);
+        define_native(&env, "pow", Some(2), native::pow);
+        define_native(&env, "abs", Some(1), native::abs);
+        define_native(&env, "floor", Some(1), native::floor);
+        define_native(&env, "ceil", Some(1), native::ceil);
+        define_native(&env, "round", Some(1), native::round);
+        define_interpreter_native(&env, "random", Some(0), native::random);
+        define_interpreter_native(&env, "randomInt", Some(2), native::random_int);
+        define_interpreter_native(&env, "seedRandom", Some(1), native::seed_random);
+        define_interpreter_native(&env, "assert", None, native::assert);
+        define_interpreter_native(&env, "write", Some(1), native::write);
+        // 依赖系统时间和标准输入的内置函数，no-std 特性下不提供
+        #[cfg(not(feature = "no-std"))]
+        {
+            define_native(&env, "clock", Some(0), native::clock);
+            define_native(&env, "clockMillis", Some(0), native::clock_millis);
+            define_native(&env, "readLine", Some(0), native::read_line);
+            define_interpreter_native(&env, "breakpoint", Some(0), native::breakpoint);
+            define_interpreter_native(&env, "sleep", Some(1), native::sleep);
+        }
 
         Self {
+            globals: Rc::clone(&env),
             environment: env,
+            locals: HashMap::new(),
             instance_counter: 0,
+            comparison_mode: ComparisonMode::default(),
+            string_plus_coerces: false,
+            max_string_length: None,
+            output: Box::new(io::stdout()),
+            interactive_debug: false,
+            in_debugger: false,
+            repl_mode: false,
+            deterministic: false,
+            profile: None,
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            rng_state: Self::mix_seed(initial_seed()),
+            native_call_line: 0,
         }
     }
 
-    // 主控流程，解释每一个表达式
+    pub fn set_comparison_mode(&mut self, mode: ComparisonMode) {
+        self.comparison_mode = mode;
+    }
+
+    /// 开启后 `"count: " + 5` 会把非字符串一侧（数字或布尔）转为显示形式再拼接，
+    /// 默认关闭以保持 Lox 的严格语义
+    pub fn set_string_plus_coerces(&mut self, enabled: bool) {
+        self.string_plus_coerces = enabled;
+    }
+
+    /// 限制运行时拼接出的字符串长度（字节数），用于运行不可信代码时防止内存耗尽
+    pub fn set_max_string_length(&mut self, limit: Option<usize>) {
+        self.max_string_length = limit;
+    }
+
+    /// 对语句做变量解析并记录局部变量深度，须在 `interpret` 之前调用；
+    /// 未经解析的变量引用一律按全局变量查找
+    pub fn resolve(&mut self, statements: &[Stmt]) -> std::result::Result<(), Vec<ParseError>> {
+        let locals = Resolver::new().resolve(statements)?;
+        self.locals.extend(locals);
+        Ok(())
+    }
+
+    /// 开启后 breakpoint() 会暂停执行并进入调试 REPL，关闭时 breakpoint() 不做任何事
+    pub fn set_interactive_debug(&mut self, enabled: bool) {
+        self.interactive_debug = enabled;
+    }
+
+    /// 确定性模式下 sleep() 只检查参数并立即返回，随机数从固定种子开始，便于测试和回放
+    pub fn set_deterministic(&mut self, enabled: bool) {
+        self.deterministic = enabled;
+        if enabled {
+            self.seed_random(0);
+        }
+    }
+
+    /// 重新设置随机数种子，相同的种子产生相同的 random()/randomInt() 序列
+    pub fn seed_random(&mut self, seed: u64) {
+        self.rng_state = Self::mix_seed(seed);
+    }
+
+    // 用 splitmix64 打散种子，相近的种子也能得到差别很大的序列；结果为 0 时换成固定的非零值
+    fn mix_seed(seed: u64) -> u64 {
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        if z == 0 { 0x2545_F491_4F6C_DD1D } else { z }
+    }
+
+    /// 下一个 [0, 1) 内的随机数（xorshift64*，取高 53 位）
+    pub(crate) fn next_random(&mut self) -> f64 {
+        let mut x = self.rng_state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng_state = x;
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    #[cfg(not(feature = "no-std"))]
+    pub(crate) fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// REPL 模式下，顶层的表达式语句（包括赋值）会打印其值；执行脚本时保持关闭
+    pub fn set_repl_mode(&mut self, enabled: bool) {
+        self.repl_mode = enabled;
+    }
+
+    /// 函数调用层数上限（默认 DEFAULT_MAX_CALL_DEPTH），超过时报可捕获的运行时错误。
+    /// 上限只有在宿主线程的栈容得下时才能先于真正的栈溢出生效：按每层约 11KB（调试构建）
+    /// 估算，默认上限需要约 11MB，超过主线程默认的 8MB。命令行在足够大的独立线程上运行；
+    /// 嵌入时应在栈足够大的线程中运行解释器，或相应调小上限
+    pub fn set_max_call_depth(&mut self, limit: usize) {
+        self.max_call_depth = limit;
+    }
+
+    /// 开启后统计每个用户函数的调用次数和累计耗时，见 profile_report
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(HashMap::new);
+    }
+
+    /// 性能分析表：按累计耗时从高到低排列，未开启时返回 None。
+    /// 累计耗时包含被调用函数的时间
+    pub fn profile_report(&self) -> Option<String> {
+        let profile = self.profile.as_ref()?;
+        let mut entries: Vec<(&String, &ProfileEntry)> = profile.iter().collect();
+        entries.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(b.0)));
+
+        let mut report = format!("{:<24} {:>10} {:>12}\n", "function", "calls", "total ms");
+        for (name, entry) in entries {
+            report.push_str(&format!(
+                "{:<24} {:>10} {:>12.3}\n",
+                name,
+                entry.calls,
+                entry.total.as_secs_f64() * 1000.0
+            ));
+        }
+        Some(report)
+    }
+
+    /// 替换 print 语句的输出目标
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    // 向 print 的输出目标写入文本（不换行）并刷新，供 write() 使用
+    pub(crate) fn write_output(&mut self, text: &str) -> Result<()> {
+        write!(self.output, "{}", text).map_err(output_error)?;
+        self.output.flush().map_err(output_error)
+    }
+
+    /// 解析并执行语句，把这期间的输出收集为字符串返回，结束后（包括出错时）恢复原输出目标。
+    /// 与 `interpret_source` 一样先做变量解析，传入未经 `resolve` 的语法树也可以；
+    /// 解析出错时不执行任何语句，错误信息作为运行时错误返回
+    pub fn run_collecting(&mut self, stmts: &[Stmt]) -> (Result<()>, String) {
+        if let Err(errors) = self.resolve(stmts) {
+            return (Err(RuntimeError::Runtime(render_parse_errors(&errors))), String::new());
+        }
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let previous = std::mem::replace(
+            &mut self.output,
+            Box::new(SharedBuffer(Rc::clone(&buffer))),
+        );
+        let result = self.interpret(stmts);
+        self.output = previous;
+
+        let captured = String::from_utf8_lossy(&buffer.borrow()).into_owned();
+        (result, captured)
+    }
+
+    /// 依次执行语句。语句须先经过 `resolve` 解析，否则块和函数中的局部变量
+    /// 会按全局变量查找而报 Undefined variable；需要一步完成时使用 `interpret_source`
     pub fn interpret(&mut self, statements: &[Stmt]) -> Result<()> {
         for stmt in statements {
-            self.execute(stmt)?;
-
+            match stmt {
+                // 只有直接输入的顶层表达式语句才回显，函数体和代码块内的不受影响
+                Stmt::Expression { expression } if self.repl_mode => {
+                    let value = self.evaluate(expression)?;
+                    let text = stringify(&value);
+                    writeln!(self.output, "{}", text).map_err(output_error)?;
+                }
+                _ => self.execute(stmt)?,
+            }
         }
         Ok(())
     }
 
+    /// 扫描、解析并执行一段源码，复用当前解释器的全局环境，
+    /// 因此多次调用之间定义的变量、函数和类会保留（用于 REPL 等场景）
+    pub fn interpret_source(&mut self, source: &str) -> std::result::Result<(), UnifiedError> {
+        let tokens = Scanner::new(source).scan_tokens().map_err(UnifiedError::Scan)?;
+        let mut parser = Parser::new(tokens);
+        // REPL 中一行末尾的表达式可以不写分号
+        let statements = if self.repl_mode {
+            parser.parse_repl()?
+        } else {
+            parser.parse()?
+        };
+        self.resolve(&statements)?;
+        self.interpret(&statements)?;
+        Ok(())
+    }
+
+    /// 程序加载完成后调用顶层的 main() 函数；main 接受一个参数时，
+    /// `args` 以字符串列表传入。不存在 main 时，`required` 为 true 则报错，否则什么也不做
+    pub fn call_main(&mut self, required: bool, args: &[String]) -> Result<()> {
+        let main_token = Token::new_identifier("main".to_string());
+        let main = self.environment.borrow().get(&main_token);
+        match main {
+            Ok(Literal::FunctionValue(func)) => {
+                let strings = args.iter().map(|arg| Literal::StringValue(arg.as_str().into()));
+                let call_args = if func.has_rest {
+                    // main(...args) 的 rest 参数同样收到全部参数组成的列表
+                    strings.collect()
+                } else if func.params.len() == 1 {
+                    vec![Literal::ListValue(Rc::new(RefCell::new(strings.collect())))]
+                } else {
+                    Vec::new()
+                };
+                self.call_function(&func, call_args, &main_token)?;
+                Ok(())
+            }
+            _ if required => Err(RuntimeError::Runtime(
+                "Program has no 'main' function.".into(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // 只负责分派：各分支放在单独的方法中，使递归经过的 evaluate 栈帧保持较小，
+    // 深层嵌套的表达式和递归调用占用的宿主栈空间随之减少
     fn evaluate(&mut self, expr: &Expr) -> Result<Literal> {
         match expr {
             Expr::Literal { value } => Ok(value.clone()),
-            Expr::Variable { name } => self.environment.borrow().get(name),
+            Expr::Variable { id, name } => self.look_up_variable(*id, name),
             Expr::Grouping { expression } => self.evaluate(expression),
-            Expr::Unary { operator, right } => {
-                let right_val = self.evaluate(right)?;
-                match operator.token_type {
-                    TokenType::Minus => self
-                        .check_number_operand(&right_val)
-                        .map(|n| Literal::NumberValue(-n)),
-                    TokenType::Bang => Ok(Literal::Boolean(!self.is_truthy(&right_val))),
-                    _ => unreachable!(),
-                }
-            }
+            Expr::Unary { operator, right } => self.evaluate_unary(operator, right),
             Expr::Binary {
                 left,
                 operator,
                 right,
-            } => {
-                let left_val = self.evaluate(left)?;
-                let right_val = self.evaluate(right)?;
-
-                match operator.token_type {
-                    // 算术运算
-                    TokenType::Plus => self.add_values(&left_val, &right_val),
-                    TokenType::Minus => self.sub_numbers(&left_val, &right_val),
-                    TokenType::Star => self.mul_numbers(&left_val, &right_val),
-                    TokenType::Slash => self.div_numbers(&left_val, &right_val),
-                    // 比较运算
-                    TokenType::Greater => self.compare(&left_val, &right_val, |a, b| a > b),
-                    TokenType::GreaterEqual => self.compare(&left_val, &right_val, |a, b| a >= b),
-                    TokenType::Less => self.compare(&left_val, &right_val, |a, b| a < b),
-                    TokenType::LessEqual => self.compare(&left_val, &right_val, |a, b| a <= b),
-                    // 逻辑运算
-                    TokenType::EqualEqual => {
-                        Ok(Literal::Boolean(self.is_equal(&left_val, &right_val)))
-                    }
-                    TokenType::BangEqual => {
-                        Ok(Literal::Boolean(!self.is_equal(&left_val, &right_val)))
-                    }
-                    TokenType::And => self.logical_and(&left_val, &right_val),
-                    TokenType::Or => self.logical_or(&left_val, &right_val),
-                    _ => Err(RuntimeError::Runtime(
-                        "Invalid operator.".into(),
-                    )),
-                }
-            }
-            // 其他表达式类型...
+            } => self.evaluate_binary(left, operator, right),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => self.evaluate_logical(left, operator, right),
             Expr::Call {
                 callee,
                 paren,
                 arguments,
-            } => {
-                let callee_val = self.evaluate(callee)?;
-                let args = self.evaluate_args(arguments)?;
-
-                match callee_val {
-                    Literal::FunctionValue(func) => self.call_function(&func, args, paren),
-                    Literal::ClassValue(cls) => {
-                        // 类实例化调用
-                        let instance = self.call_class_constructor(&cls, args, paren)?;
-                        Ok(instance)
-                    }
-                    // 处理实例方法调用
-                    Literal::InstanceValue(inst) => {
-                        let method_name = self.get_call_name(callee);
-                        // println!("flag for this.");
-                        if let Some(Literal::FunctionValue(func)) = inst.class.find_method(&method_name) {
-                            let bound_func = func.bind(&inst);
-                            self.call_function(&bound_func, args, paren)
-                        } else {
-                            Err(RuntimeError::Runtime(
-                                format!("Undefined property '{}'.", method_name),
-                            ))
-                        }
-                    }
-                    Literal::NativeFunctionValue(func) => {
-                        // 调用原生函数
-                        func(&args)
-                    }
-                    _ => Err(RuntimeError::Runtime(
-                        "Can only call functions and classes.".into(),
-                    )),
-                }
+                spread,
+            } => self.evaluate_call(callee, paren, arguments, spread),
+            Expr::Super { id, keyword, method } => self.evaluate_super(*id, keyword, method),
+            Expr::GetAttribute { object, name } => self.evaluate_get(object, name),
+            // 变量赋值表达式
+            Expr::Assign { id, name, value } => self.evaluate_assign(*id, name, value),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => self.evaluate_set(object, name, value),
+            Expr::ListLiteral { elements } => self
+                .evaluate_args(elements)
+                .map(|values| Literal::ListValue(Rc::new(RefCell::new(values)))),
+            Expr::MapLiteral { entries, brace } => self.evaluate_map_literal(entries, brace),
+            Expr::Index {
+                object,
+                index,
+                bracket,
+            } => self.evaluate_index(object, index, bracket),
+            Expr::SetIndex {
+                object,
+                index,
+                bracket,
+                value,
+            } => self.evaluate_set_index(object, index, bracket, value),
+            Expr::CompoundAssign {
+                target,
+                operator,
+                value,
+            } => self.evaluate_compound_assign(target, operator, value),
+            Expr::Lambda { params, has_rest, body } => Ok(self.evaluate_lambda(params, *has_rest, body)),
+            Expr::ClassExpr {
+                superclass,
+                methods,
+                statics,
+                fields,
+            } => self.evaluate_class_expr(superclass, methods, statics, fields),
+            Expr::This { id, keyword } => self.evaluate_this(*id, keyword),
+        }
+    }
+
+    fn evaluate_unary(&mut self, operator: &Token, right: &Expr) -> Result<Literal> {
+        let right_val = self.evaluate(right)?;
+        match operator.token_type {
+            TokenType::Minus => self
+                .check_number_operand(operator, &right_val)
+                .map(|n| Literal::NumberValue(-n)),
+            TokenType::Bang => Ok(Literal::Boolean(!self.is_truthy(&right_val))),
+            TokenType::Tilde => {
+                bitwise_operand(&right_val, operator).map(|n| Literal::NumberValue(!n as f64))
             }
-            Expr::Super { keyword, method } => {
-                // 步骤1：获取超类引用
-                let super_class = match self.environment.borrow().get(keyword)? {
-                    Literal::ClassValue(c) => c,
-                    _ => {
-                        return Err(RuntimeError::Runtime(
-                            "Invalid super class.".into(),
-                        ));
-                    }
-                };
+            // typeof 返回操作数的类型名
+            TokenType::TypeOf => Ok(Literal::StringValue(right_val.type_name().into())),
+            _ => unreachable!(),
+        }
+    }
 
-                // 步骤2：获取当前实例的this绑定
-                let this_instance = match self.environment.borrow().get(&Token::this())? {
-                    Literal::InstanceValue(i) => i,
-                    _ => {
-                        return Err(RuntimeError::Runtime(
-                            "super must be used in instance method.".into(),
-                        ));
-                    }
-                };
+    fn evaluate_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<Literal> {
+        let left_val = self.evaluate(left)?;
+        let right_val = self.evaluate(right)?;
+        self.binary_op(&left_val, operator, &right_val)
+    }
 
-                // 步骤3：查找超类方法
-                let method = super_class.find_method(&method.lexeme).ok_or_else(|| {
-                    RuntimeError::Runtime(
-                        format!("Undefined property '{}'.", method.lexeme),
-                    )
-                })?;
-
-                // 步骤4：创建闭包环境
-                if let Literal::FunctionValue(func) = method {
-                    let bound_func = func.bind(&this_instance);
-                    Ok(Literal::FunctionValue(bound_func))
+    // 短路求值：or 左侧为真、and 左侧为假时直接返回左侧的值
+    fn evaluate_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<Literal> {
+        let left_val = self.evaluate(left)?;
+        let truthy = self.is_truthy(&left_val);
+        match operator.token_type {
+            TokenType::Or if truthy => Ok(left_val),
+            TokenType::And if !truthy => Ok(left_val),
+            _ => self.evaluate(right),
+        }
+    }
+
+    fn evaluate_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr], spread: &[bool]) -> Result<Literal> {
+        let callee_val = self.evaluate(callee)?;
+        let args = self.evaluate_call_args(arguments, spread, paren)?;
+
+        match callee_val {
+            // 处理实例方法调用
+            Literal::InstanceValue(inst) => {
+                let method_name = self.get_call_name(callee);
+                // println!("flag for this.");
+                if let Some(Literal::FunctionValue(func)) = inst.class.find_method(&method_name) {
+                    let bound_func = func.bind(&inst);
+                    self.call_function(&bound_func, args, paren)
                 } else {
-                    // 使用调用方法时的方法名 Token 来构建错误
-                    Err(RuntimeError::Runtime(
-                        format!("'{}' is not a function.", keyword.lexeme).into(),
-                    ))
+                    Err(error_at(paren, &format!("Undefined property '{}'.", method_name)))
                 }
             }
+            callee_val => self.call_value(callee_val, args, paren),
+        }
+    }
+
+    fn evaluate_super(&mut self, id: usize, keyword: &Token, method: &Token) -> Result<Literal> {
+        // 步骤1：获取超类引用（super 绑定在定义方法的类环境中）
+        let (depth, slot) = match self.locals.get(&id) {
+            Some(&location) => location,
+            None => {
+                return Err(error_at(keyword, "Can't use 'super' in a class with no superclass."));
+            }
+        };
+        let super_class = match self.environment.borrow().get_at(depth, slot, keyword)? {
+            Literal::ClassValue(c) => c,
+            _ => {
+                return Err(error_at(keyword, "Invalid super class."));
+            }
+        };
+
+        // 步骤2：获取当前实例的this绑定（this 环境紧挨在类环境之内，this 是其中唯一的变量）
+        let this_instance = match self.environment.borrow().get_at(depth - 1, 0, &Token::this())? {
+            Literal::InstanceValue(i) => i,
+            _ => {
+                return Err(error_at(keyword, "super must be used in instance method."));
+            }
+        };
+
+        // 步骤3：查找超类方法
+        let found = super_class
+            .find_method(&method.lexeme)
+            .ok_or_else(|| error_at(method, &format!("Undefined property '{}'.", method.lexeme)))?;
+
+        // 步骤4：创建闭包环境（getter 直接调用）
+        if let Literal::FunctionValue(func) = found {
+            let bound_func = func.bind(&this_instance);
+            if bound_func.is_getter {
+                return self.call_function(&bound_func, Vec::new(), method);
+            }
+            Ok(Literal::FunctionValue(Rc::new(bound_func)))
+        } else {
+            // 使用调用方法时的方法名 Token 来构建错误
+            Err(error_at(method, &format!("'{}' is not a function.", keyword.lexeme)))
+        }
+    }
+
+    fn evaluate_get(&mut self, object: &Expr, name: &Token) -> Result<Literal> {
+        let obj = self.evaluate(object)?;
+        self.get_attribute(obj, name)
+    }
+
+    fn evaluate_assign(&mut self, id: usize, name: &Token, value: &Expr) -> Result<Literal> {
+        let val = self.evaluate(value)?;
+        match self.locals.get(&id) {
+            Some(&(depth, slot)) => self.environment.borrow_mut().assign_at(depth, slot, name, val.clone())?,
+            None if self.in_debugger => self.environment.borrow_mut().assign(name, val.clone())?,
+            None => self.globals.borrow_mut().assign(name, val.clone())?,
+        }
+        Ok(val)
+    }
+
+    fn evaluate_set(&mut self, object: &Expr, name: &Token, value: &Expr) -> Result<Literal> {
+        let obj = self.evaluate(object)?;
+        let val = self.evaluate(value)?;
+
+        if let Literal::InstanceValue(instance) = obj {
+            instance.environment.borrow_mut().define(name.lexeme.clone(), val.clone());
+            Ok(Literal::InstanceValue(instance))
+        } else {
+            Err(error_at(name, "Only instances can have fields."))
+        }
+    }
+
+    fn evaluate_map_literal(&mut self, entries: &[(Expr, Expr)], brace: &Token) -> Result<Literal> {
+        let mut map = HashMap::new();
+        for (key, value) in entries {
+            let key = self.evaluate(key)?;
+            let key = map_key(&key, brace)?.to_string();
+            let value = self.evaluate(value)?;
+            map.insert(key, value);
+        }
+        Ok(Literal::MapValue(Rc::new(RefCell::new(map))))
+    }
+
+    fn evaluate_index(&mut self, object: &Expr, index: &Expr, bracket: &Token) -> Result<Literal> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        get_index(&object, &index, bracket)
+    }
+
+    fn evaluate_set_index(&mut self, object: &Expr, index: &Expr, bracket: &Token, value: &Expr) -> Result<Literal> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        let val = self.evaluate(value)?;
+        set_index(&object, &index, bracket, val.clone())?;
+        Ok(val)
+    }
+
+    fn evaluate_compound_assign(&mut self, target: &Expr, operator: &Token, value: &Expr) -> Result<Literal> {
+        match target {
+            // 与 obj.name = obj.name + value 相同（obj 只求值一次）：属性由 getter 提供时，
+            // 读取经过 getter，写入则定义同名字段，此后该字段遮蔽 getter
             Expr::GetAttribute { object, name } => {
                 let obj = self.evaluate(object)?;
-                if let Literal::InstanceValue(instance) = obj {
-                    // 尝试获取字段
-                    match instance.environment.borrow_mut().get(name) {
-                        Ok(field) => Ok(field),
-                        Err(_) => {
-                            // 字段不存在，查找方法并绑定实例
-                            // println!("flag2 for this.");
-                            if let Some(Literal::FunctionValue(func)) = instance.class.find_method(&name.lexeme) {
-                                let bound_func = func.bind(&instance);
-                                Ok(Literal::FunctionValue(bound_func))
-                            } else {
-                                Err(RuntimeError::Runtime(
-                                    format!("Undefined property '{}'.", name.lexeme),
-                                ))
-                            }
-                        }
-                    }
-                } else {
-                    Err(RuntimeError::Runtime(
-                        "Only instances have attributes.".into(),
-                    ))
-                }
-            }
-            // 变量赋值表达式
-            Expr::Assign { name, value } => {
-                let val = self.evaluate(value)?;
-                self.environment.borrow_mut().assign(name, val.clone())?;
-                Ok(val)
+                let Literal::InstanceValue(instance) = &obj else {
+                    return Err(error_at(name, "Only instances can have fields."));
+                };
+                let instance = instance.clone();
+                let current = self.get_attribute(obj, name)?;
+                let rhs = self.evaluate(value)?;
+                let result = self.binary_op(&current, operator, &rhs)?;
+                instance.environment.borrow_mut().define(name.lexeme.clone(), result.clone());
+                Ok(result)
             }
-            Expr::Set {
+            Expr::Index {
                 object,
-                name,
-                value,
+                index,
+                bracket,
             } => {
-                let obj = self.evaluate(object)?;
-                let val = self.evaluate(value)?;
-
-                if let Literal::InstanceValue(instance) = obj {
-                    instance.environment.borrow_mut().define(name.lexeme.clone(), val.clone());
-                    Ok(Literal::InstanceValue(instance))
-                } else {
-                    Err(RuntimeError::Runtime(
-                        "Only instances can have fields.".into(),
-                    ))
-                }
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let current = get_index(&object, &index, bracket)?;
+                let rhs = self.evaluate(value)?;
+                let result = self.binary_op(&current, operator, &rhs)?;
+                set_index(&object, &index, bracket, result.clone())?;
+                Ok(result)
             }
-            Expr::This { keyword } => {
-                // 从当前环境获取this绑定
-                // self.environment.check_this_binding(format!("Checking 'this' at line {}", keyword.line));
-                let this_value = self.environment.borrow().get(keyword)?;
+            // 解析器只为属性和下标生成 CompoundAssign
+            _ => unreachable!(),
+        }
+    }
 
-                // 验证必须是实例类型
-                if let Literal::InstanceValue(instance) = this_value {
-                    Ok(Literal::InstanceValue(instance))
-                } else {
-                    Err(RuntimeError::Runtime(
-                        "Invalid 'this' context.".into(),
-                    ))
-                }
-            }
+    // 与函数声明相同地捕获当前环境，只是不绑定名字
+    fn evaluate_lambda(&mut self, params: &[Token], has_rest: bool, body: &[Stmt]) -> Literal {
+        Literal::FunctionValue(Rc::new(LoxFunction {
+            name: "<lambda>".to_string(),
+            params: params.to_vec(),
+            has_rest,
+            body: Rc::new(body.to_vec()),
+            closure: Rc::clone(&self.environment),
+            is_initializer: false,
+            is_getter: false,
+        }))
+    }
+
+    fn evaluate_class_expr(
+        &mut self,
+        superclass: &Option<Box<Expr>>,
+        methods: &[Stmt],
+        statics: &[Stmt],
+        fields: &[Stmt],
+    ) -> Result<Literal> {
+        let class = self.create_class("anonymous", superclass, methods, statics, fields)?;
+        Ok(Literal::ClassValue(Rc::new(class)))
+    }
+
+    fn evaluate_this(&mut self, id: usize, keyword: &Token) -> Result<Literal> {
+        // 从当前环境获取this绑定
+        // self.environment.check_this_binding(format!("Checking 'this' at line {}", keyword.line));
+        let this_value = self.look_up_variable(id, keyword)?;
+
+        // 验证必须是实例类型
+        if let Literal::InstanceValue(instance) = this_value {
+            Ok(Literal::InstanceValue(instance))
+        } else {
+            Err(error_at(keyword, "Invalid 'this' context."))
         }
     }
 
 
-    fn is_truthy(&self, val: &Literal) -> bool {
+    // 解析过的局部变量按深度和槽位精确查找，其余视为全局变量
+    fn look_up_variable(&self, id: usize, name: &Token) -> Result<Literal> {
+        match self.locals.get(&id) {
+            Some(&(depth, slot)) => self.environment.borrow().get_at(depth, slot, name),
+            None if self.in_debugger => self.environment.borrow().get(name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    pub(crate) fn is_truthy(&self, val: &Literal) -> bool {
         match val {
             Literal::Nil => false,
             Literal::Boolean(b) => *b,
@@ -263,100 +903,153 @@ impl Interpreter {
         }
     }
 
-    fn check_number_operand(&self, val: &Literal) -> Result<f64> {
+    // 一元运算的操作数检查，报错时带上运算符、行号和实际类型
+    fn check_number_operand(&self, operator: &Token, val: &Literal) -> Result<f64> {
         if let Literal::NumberValue(n) = val {
             Ok(*n)
         } else {
-            Err(RuntimeError::Runtime(
-                "Operand must be a number.".into(),
+            Err(error_at(
+                operator,
+                &format!("Operand of unary '{}' must be a number but got {}.", operator.lexeme, val.type_name()),
             ))
         }
     }
 
-    // 实现加法（支持字符串连接）
-    fn add_values(&self, a: &Literal, b: &Literal) -> Result<Literal> {
-        match (a, b) {
-            (Literal::NumberValue(n1), Literal::NumberValue(n2)) => {
-                Ok(Literal::NumberValue(n1 + n2))
+    // 二元运算，Binary 表达式与复合赋值共用
+    fn binary_op(&self, left_val: &Literal, operator: &Token, right_val: &Literal) -> Result<Literal> {
+        match operator.token_type {
+            // 算术运算
+            TokenType::Plus => self.add_values(left_val, right_val, operator),
+            TokenType::Minus => self.sub_numbers(left_val, right_val, operator),
+            TokenType::Star => self.mul_numbers(left_val, right_val, operator),
+            TokenType::Slash => self.div_numbers(left_val, right_val, operator),
+            TokenType::Percent => self.mod_numbers(left_val, right_val, operator),
+            // 位运算
+            TokenType::Ampersand
+            | TokenType::Pipe
+            | TokenType::Caret
+            | TokenType::LessLess
+            | TokenType::GreaterGreater => bitwise(left_val, right_val, operator),
+            // 比较运算
+            TokenType::Greater => self.compare(left_val, right_val, operator, |a, b| a > b),
+            TokenType::GreaterEqual => self.compare(left_val, right_val, operator, |a, b| a >= b),
+            TokenType::Less => self.compare(left_val, right_val, operator, |a, b| a < b),
+            TokenType::LessEqual => self.compare(left_val, right_val, operator, |a, b| a <= b),
+            // 逻辑运算
+            TokenType::EqualEqual => {
+                Ok(Literal::Boolean(self.is_equal(left_val, right_val)))
             }
-            (Literal::StringValue(s1), Literal::StringValue(s2)) => {
-                Ok(Literal::StringValue(format!("{}{}", s1, s2)))
+            TokenType::BangEqual => {
+                Ok(Literal::Boolean(!self.is_equal(left_val, right_val)))
             }
-            _ => Err(RuntimeError::Runtime(
-                "Operands must be two numbers or two strings.".into(),
-            )),
+            _ => Err(error_at(operator, "Invalid operator.")),
         }
     }
 
-    fn sub_numbers(&self, left: &Literal, right: &Literal) -> Result<Literal> {
-        let (a, b) = self.check_number_operands(left, right)?;
-        Ok(Literal::NumberValue(a - b))
-    }
-
-    fn mul_numbers(&self, left: &Literal, right: &Literal) -> Result<Literal> {
-        let (a, b) = self.check_number_operands(left, right)?;
-        Ok(Literal::NumberValue(a * b))
-    }
-
-    fn div_numbers(&self, left: &Literal, right: &Literal) -> Result<Literal> {
-        let (a, b) = self.check_number_operands(left, right)?;
-        if b == 0.0 {
-            return Err(RuntimeError::Runtime("Division by zero.".into()));
+    // 读取属性：实例的字段或绑定了实例的方法（getter 直接调用），以及类的 static 方法
+    fn get_attribute(&mut self, obj: Literal, name: &Token) -> Result<Literal> {
+        if let Literal::InstanceValue(instance) = obj {
+            // 尝试获取字段（先释放借用，getter 中可能读写字段）
+            let field = instance.environment.borrow().get(name);
+            match field {
+                Ok(field) => Ok(field),
+                Err(_) => {
+                    // 字段不存在，查找方法并绑定实例
+                    // println!("flag2 for this.");
+                    if let Some(Literal::FunctionValue(func)) = instance.class.find_method(&name.lexeme) {
+                        let bound_func = func.bind(&instance);
+                        // getter 在访问时立即调用，返回其结果
+                        if bound_func.is_getter {
+                            return self.call_function(&bound_func, Vec::new(), name);
+                        }
+                        Ok(Literal::FunctionValue(Rc::new(bound_func)))
+                    } else {
+                        Err(error_at(name, &format!("Undefined property '{}'.", name.lexeme)))
+                    }
+                }
+            }
+        } else if let Literal::ClassValue(class) = obj {
+            // 通过类访问 static 方法，不绑定实例
+            match class.find_static(&name.lexeme) {
+                Some(func) => Ok(Literal::FunctionValue(Rc::new(func))),
+                None if class.find_method(&name.lexeme).is_some() => Err(error_at(
+                    name,
+                    &format!(
+                        "'{}' is an instance method of '{}' and can't be called on the class.",
+                        name.lexeme, class.name
+                    ),
+                )),
+                None => Err(error_at(
+                    name,
+                    &format!("Undefined static method '{}' on class '{}'.", name.lexeme, class.name),
+                )),
+            }
+        } else {
+            Err(error_at(name, "Only instances have attributes."))
         }
-        Ok(Literal::NumberValue(a / b))
     }
 
-    fn is_equal(&self, a: &Literal, b: &Literal) -> bool {
+    // 实现加法（支持字符串连接）
+    fn add_values(&self, a: &Literal, b: &Literal, operator: &Token) -> Result<Literal> {
         match (a, b) {
-            // Nil只等于Nil
-            (Literal::Nil, Literal::Nil) => true,
-
-            // 布尔值严格比较
-            (Literal::Boolean(a), Literal::Boolean(b)) => a == b,
-
-            // 数值比较
-            (Literal::NumberValue(a), Literal::NumberValue(b)) => (a - b).abs() < f64::EPSILON,
-
-            // 字符串内容比较
-            (Literal::StringValue(a), Literal::StringValue(b)) => a == b,
+            (Literal::NumberValue(n1), Literal::NumberValue(n2)) => {
+                Ok(Literal::NumberValue(n1 + n2))
+            }
+            (Literal::StringValue(s1), Literal::StringValue(s2)) => self.concat(s1, s2, operator),
+            (Literal::StringValue(s), other @ (Literal::NumberValue(_) | Literal::Boolean(_)))
+                if self.string_plus_coerces =>
+            {
+                self.concat(s, &stringify(other), operator)
+            }
+            (other @ (Literal::NumberValue(_) | Literal::Boolean(_)), Literal::StringValue(s))
+                if self.string_plus_coerces =>
+            {
+                self.concat(&stringify(other), s, operator)
+            }
+            _ => Err(error_at(operator, "Operands must be two numbers or two strings.")),
+        }
+    }
 
-            // 函数比较（指针地址比较）
-            (Literal::FunctionValue(a), Literal::FunctionValue(b)) => std::ptr::eq(a, b),
+    // 字符串拼接，先检查长度上限再分配
+    fn concat(&self, left: &str, right: &str, operator: &Token) -> Result<Literal> {
+        if self.max_string_length.is_some_and(|limit| left.len() + right.len() > limit) {
+            return Err(error_at(operator, "String length limit exceeded."));
+        }
+        Ok(Literal::StringValue(format!("{}{}", left, right).into()))
+    }
 
-            // 类比较
-            (Literal::ClassValue(a), Literal::ClassValue(b)) => {
-                a.name == b.name && std::ptr::eq(a, b)
-            }
+    fn sub_numbers(&self, left: &Literal, right: &Literal, operator: &Token) -> Result<Literal> {
+        let (a, b) = self.check_number_operands(left, right, operator)?;
+        Ok(Literal::NumberValue(a - b))
+    }
 
-            // 其他情况均为不相等
-            _ => false,
-        }
+    fn mul_numbers(&self, left: &Literal, right: &Literal, operator: &Token) -> Result<Literal> {
+        let (a, b) = self.check_number_operands(left, right, operator)?;
+        Ok(Literal::NumberValue(a * b))
     }
 
-    fn as_bool(&self, val: &Literal) -> Result<bool> {
-        match val {
-            Literal::Boolean(b) => Ok(*b),
-            _ => Err(RuntimeError::Runtime(
-                format!("Operand must be boolean (got {}).", val.type_name()),
-            )),
+    fn div_numbers(&self, left: &Literal, right: &Literal, operator: &Token) -> Result<Literal> {
+        let (a, b) = self.check_number_operands(left, right, operator)?;
+        if b == 0.0 {
+            return Err(error_at(operator, "Division by zero."));
         }
+        Ok(Literal::NumberValue(a / b))
     }
 
-    // 逻辑与运算
-    fn logical_and(&self, a: &Literal, b: &Literal) -> Result<Literal> {
-        let a_bool = self.as_bool(a)?;
-        let b_bool = self.as_bool(b)?;
-        Ok(Literal::Boolean(a_bool && b_bool))
+    // 取余，结果符号与被除数相同（同 Rust 的 f64 %）
+    fn mod_numbers(&self, left: &Literal, right: &Literal, operator: &Token) -> Result<Literal> {
+        let (a, b) = self.check_number_operands(left, right, operator)?;
+        if b == 0.0 {
+            return Err(error_at(operator, "Modulo by zero."));
+        }
+        Ok(Literal::NumberValue(a % b))
     }
 
-    // 逻辑或运算
-    fn logical_or(&self, a: &Literal, b: &Literal) -> Result<Literal> {
-        let a_bool = self.as_bool(a)?;
-        let b_bool = self.as_bool(b)?;
-        Ok(Literal::Boolean(a_bool || b_bool))
+    fn is_equal(&self, a: &Literal, b: &Literal) -> bool {
+        a == b
     }
 
-    fn compare<T>(&self, left: &Literal, right: &Literal, comp: T) -> Result<Literal>
+    fn compare<T>(&self, left: &Literal, right: &Literal, operator: &Token, comp: T) -> Result<Literal>
     where
         T: Fn(f64, f64) -> bool,
     {
@@ -365,235 +1058,568 @@ impl Interpreter {
                 Ok(Literal::Boolean(comp(*a, *b)))
             }
             (Literal::StringValue(a), Literal::StringValue(b)) => {
-                Ok(Literal::Boolean(comp(a.len() as f64, b.len() as f64)))
+                Ok(Literal::Boolean(comp(comp_str(a, b), 0.0)))
+            }
+            // 宽松模式：字符串转数字后比较
+            (Literal::NumberValue(a), Literal::StringValue(b))
+                if self.comparison_mode == ComparisonMode::Loose =>
+            {
+                Ok(Literal::Boolean(comp(*a, self.coerce_to_number(b, operator)?)))
+            }
+            (Literal::StringValue(a), Literal::NumberValue(b))
+                if self.comparison_mode == ComparisonMode::Loose =>
+            {
+                Ok(Literal::Boolean(comp(self.coerce_to_number(a, operator)?, *b)))
             }
-            _ => Err(RuntimeError::Runtime(
-                "Operands must be numbers or strings.".into(),
+            _ => Err(error_at(
+                operator,
+                &format!("Cannot order {} and {}.", left.type_name(), right.type_name()),
             )),
         }
     }
 
+    fn coerce_to_number(&self, s: &str, operator: &Token) -> Result<f64> {
+        s.trim().parse::<f64>().map_err(|_| {
+            error_at(operator, &format!("Cannot compare number with non-numeric string '{}'.", s))
+        })
+    }
+
     // 公共类型检查方法
     fn check_number_operands(
         &self,
         left: &Literal,
         right: &Literal,
+        operator: &Token,
     ) -> Result<(f64, f64)> {
         if let (Literal::NumberValue(a), Literal::NumberValue(b)) = (left, right) {
             Ok((*a, *b))
         } else {
-            Err(RuntimeError::Runtime(
-                "Operands must be two numbers or two strings.".into(),
-            ))
+            Err(error_at(operator, "Operands must be two numbers or two strings."))
         }
     }
 
+    // 与 evaluate 相同，只负责分派，各语句的执行放在单独的方法中
     fn execute(&mut self, stmt: &Stmt) -> Result<()> {
         match stmt {
-            Stmt::Expression { expression } => {
-                self.evaluate(expression)?;
-                Ok(())
-            }
-            Stmt::Print { expression } => {
-                let value = self.evaluate(expression)?;
-                println!("{}", self.stringify(value));
-                Ok(())
-            }
-            Stmt::VarDecl { name, initializer } => {
-                if self.environment.borrow().values.contains_key(&name.lexeme) {
-                    return Err(RuntimeError::Runtime(
-                        format!("Error: Already a variable with name '{}' in this scope.", name.lexeme)
-                    ));
-                }
-                
-                let value = match initializer {
-                    Some(expr) => self.evaluate(expr)?,
-                    None => Literal::Nil,
-                };
-                self.environment.borrow_mut().define(name.lexeme.clone(), value);
-                Ok(())
-            }
-            Stmt::Block { statements } => {
-                // 保存当前环境
-                let previous_env = self.environment.clone();
-                
-                // 创建新环境（继承当前环境）
-                self.environment = Environment::new(Some(previous_env));
-                
-                // 执行块内语句
-                let result = self.execute_block(statements);
-                
-                // 恢复父环境
-                let restore = self.environment.borrow().enclosing.as_ref().unwrap().clone();
-                self.environment = restore;
-                
-                result
+            Stmt::Expression { expression } => self.evaluate(expression).map(|_| ()),
+            Stmt::Print { expression } => self.execute_print(expression),
+            Stmt::MultiVarDecl { declarations } => {
+                declarations.iter().try_for_each(|declaration| self.execute(declaration))
             }
+            Stmt::VarDecl { name, initializer } => self.execute_var_decl(name, initializer),
+            Stmt::Block { statements } => self.execute_block(statements),
             Stmt::If {
                 condition,
                 then_branch,
                 else_branch,
-            } => {
-                let cond_result = self.evaluate(condition)?;
-                if self.is_truthy(&cond_result) {
-                    self.execute(then_branch)
-                } else {
-                    else_branch.as_ref().map_or(Ok(()), |e| self.execute(e))
-                }
-            }
-            Stmt::While { condition, body } => {
-                while {
-                    let cond = self.evaluate(condition)?;
-                    self.is_truthy(&cond)
-                } {
-                    self.execute(body)?;
-                }
-                Ok(())
-            }
+            } => self.execute_if(condition, then_branch, else_branch),
+            Stmt::While { condition, body } => self.execute_while(condition, body),
+            Stmt::DoWhile { body, condition } => self.execute_do_while(body, condition),
             // 其他语句处理...
             Stmt::For {
                 initializer,
                 condition,
                 increment,
                 body,
+            } => self.execute_for(initializer, condition, increment, body),
+            Stmt::ForEach {
+                name,
+                iterable,
+                body,
+            } => self.execute_for_each(name, iterable, body),
+            Stmt::Switch {
+                discriminant,
+                cases,
+                default,
+            } => self.execute_switch(discriminant, cases, default),
+            Stmt::Function {
+                name,
+                params,
+                has_rest,
+                body,
+                ..
             } => {
-                if let Some(init) = initializer {
-                    self.execute(init.as_ref())?;
+                self.execute_function_decl(name, params, *has_rest, body);
+                Ok(())
+            }
+
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                statics,
+                fields,
+            } => self.execute_class_decl(name, superclass, methods, statics, fields),
+
+            Stmt::Return { keyword: _, value } => self.execute_return(value),
+
+            // 由所在的 while/for 捕获
+            Stmt::Break { .. } => Err(RuntimeError::Break),
+            Stmt::Continue { .. } => Err(RuntimeError::Continue),
+
+            Stmt::Try {
+                body,
+                catch_name,
+                handler,
+            } => self.execute_try(body, catch_name, handler),
+
+            // 抛出的值可被 try/catch 原样捕获，未捕获时由 main 以退出码 70 结束程序
+            Stmt::Throw { value } => self.evaluate(value).and_then(|thrown| Err(RuntimeError::UserThrow(thrown))),
+
+            Stmt::With {
+                keyword,
+                resource,
+                name,
+                body,
+            } => self.execute_with(keyword, resource, name, body),
+        }
+    }
+
+    fn execute_print(&mut self, expression: &Expr) -> Result<()> {
+        let value = self.evaluate(expression)?;
+        let text = stringify(&value);
+        writeln!(self.output, "{}", text).map_err(output_error)
+    }
+
+    fn execute_var_decl(&mut self, name: &Token, initializer: &Option<Expr>) -> Result<()> {
+        // 块内的重复声明已由变量解析报告
+        let value = match initializer {
+            Some(expr) => self.evaluate(expr)?,
+            None => Literal::Nil,
+        };
+        self.define_variable(&name.lexeme, value);
+        Ok(())
+    }
+
+    fn execute_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) -> Result<()> {
+        // else if 链逐个判断，不随分支数递归
+        let (mut condition, mut then_branch, mut else_branch) = (condition, then_branch, else_branch);
+        loop {
+            let cond_result = self.evaluate(condition)?;
+            if self.is_truthy(&cond_result) {
+                return self.execute(then_branch);
+            }
+            match else_branch.as_deref() {
+                Some(Stmt::If {
+                    condition: next_condition,
+                    then_branch: next_then,
+                    else_branch: next_else,
+                }) => {
+                    condition = next_condition;
+                    then_branch = next_then;
+                    else_branch = next_else;
                 }
+                Some(other) => return self.execute(other),
+                None => return Ok(()),
+            }
+        }
+    }
 
-                loop {
-                    let cond = match condition {
-                        Some(c) => self.evaluate(c)?,
-                        None => Literal::Boolean(true),
-                    };
-                    if !self.is_truthy(&cond) {
-                        break;
-                    }
+    fn execute_while(&mut self, condition: &Expr, body: &Stmt) -> Result<()> {
+        while {
+            let cond = self.evaluate(condition)?;
+            self.is_truthy(&cond)
+        } {
+            match self.execute(body) {
+                Err(RuntimeError::Break) => break,
+                Err(RuntimeError::Continue) => continue,
+                other => other?,
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_do_while(&mut self, body: &Stmt, condition: &Expr) -> Result<()> {
+        loop {
+            // continue 跳过本轮剩余部分，仍然检查条件
+            match self.execute(body) {
+                Err(RuntimeError::Break) => break,
+                Err(RuntimeError::Continue) => {}
+                other => other?,
+            }
+            let cond = self.evaluate(condition)?;
+            if !self.is_truthy(&cond) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_switch(&mut self, discriminant: &Expr, cases: &[(Expr, Vec<Stmt>)], default: &Option<Vec<Stmt>>) -> Result<()> {
+        let value = self.evaluate(discriminant)?;
+        for (case_value, body) in cases {
+            let case_value = self.evaluate(case_value)?;
+            if self.is_equal(&value, &case_value) {
+                return self.execute_block(body);
+            }
+        }
+        match default {
+            Some(body) => self.execute_block(body),
+            None => Ok(()),
+        }
+    }
+
+    fn execute_function_decl(&mut self, name: &Token, params: &[Token], has_rest: bool, body: &[Stmt]) {
+        // 闭包捕获定义时的环境，函数名也定义在其中，因此可以递归调用
+        let func = LoxFunction {
+            name: name.lexeme.clone(),
+            params: params.to_vec(),
+            has_rest,
+            body: Rc::new(body.to_vec()),
+            closure: Rc::clone(&self.environment),
+            is_initializer: false,
+            is_getter: false,
+        };
+
+        // 将函数绑定到当前环境
+        self.define_variable(&name.lexeme, Literal::FunctionValue(Rc::new(func)));
+    }
+
+    fn execute_class_decl(
+        &mut self,
+        name: &Token,
+        superclass: &Option<Box<Expr>>,
+        methods: &[Stmt],
+        statics: &[Stmt],
+        fields: &[Stmt],
+    ) -> Result<()> {
+        let class = self.create_class(&name.lexeme, superclass, methods, statics, fields)?;
+        self.define_variable(&name.lexeme, Literal::ClassValue(Rc::new(class)));
+        Ok(())
+    }
+
+    fn execute_return(&mut self, value: &Option<Expr>) -> Result<()> {
+        let return_value = match value {
+            Some(expr) => self.evaluate(expr)?,
+            None => Literal::Nil,
+        };
+        // 使用自定义错误类型传递返回值
+        Err(RuntimeError::Return(return_value))
+    }
+
+    fn execute_try(&mut self, body: &[Stmt], catch_name: &Token, handler: &[Stmt]) -> Result<()> {
+        // 只捕获运行时错误和抛出的值，return 等控制流信号继续向上传递；
+        // 运行时错误绑定为不带行号的错误信息字符串，throw 抛出的值保持原来的类型
+        let caught = match self.execute_block(body) {
+            Err(RuntimeError::Runtime(msg)) => Ok(Literal::StringValue(strip_line(&msg).into())),
+            Err(RuntimeError::UserThrow(value)) => Ok(value),
+            other => Err(other),
+        };
+        match caught {
+            Ok(error) => {
+                let previous = Rc::clone(&self.environment);
+                self.environment = Environment::new(Some(Rc::clone(&previous)));
+                self.environment.borrow_mut().define_local(&catch_name.lexeme, error);
+                let result = self.execute_scope(handler);
+                self.environment = previous;
+                result
+            }
+            Err(other) => other,
+        }
+    }
 
-                    self.execute(body.as_ref())?;
+    fn execute_with(&mut self, keyword: &Token, resource: &Expr, name: &Token, body: &[Stmt]) -> Result<()> {
+        let value = self.evaluate(resource)?;
+
+        let previous = Rc::clone(&self.environment);
+        self.environment = Environment::new(Some(Rc::clone(&previous)));
+        self.environment.borrow_mut().define_local(&name.lexeme, value.clone());
+        let result = self.execute_scope(body);
+        self.environment = previous;
+
+        // 无论正常结束、return 还是出错，都调用资源的 close 方法
+        let closed = match &value {
+            Literal::InstanceValue(instance) => match instance.class.find_method("close") {
+                Some(Literal::FunctionValue(close)) => self
+                    .call_function(&close.bind(instance), Vec::new(), keyword)
+                    .map(|_| ()),
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        };
+        // 代码块自身的错误优先于 close 的错误
+        result.and(closed)
+    }
 
-                    if let Some(inc) = increment {
-                        self.evaluate(inc)?;
+    // 类声明和类表达式共用的类对象构建
+    fn create_class(
+        &mut self,
+        name: &str,
+        superclass: &Option<Box<Expr>>,
+        methods: &[Stmt],
+        statics: &[Stmt],
+        fields: &[Stmt],
+    ) -> Result<LoxClass> {
+        // 解析超类
+        let super_class = match superclass {
+            Some(expr) => {
+                let val = self.evaluate(expr)?;
+                match val {
+                    Literal::ClassValue(c) => Some(c),
+                    _ => {
+                        // 超类表达式总是解析为变量引用，用其名字 Token 定位
+                        return Err(match &**expr {
+                            Expr::Variable { name, .. } => error_at(name, "Superclass must be a class."),
+                            _ => RuntimeError::Runtime("Superclass must be a class.".into()),
+                        });
                     }
                 }
-                Ok(())
             }
-            Stmt::Function {
-                name,
+            None => None,
+        };
+
+        // 创建类环境（继承当前环境）
+        let class_env = Environment::new(Some(self.environment.clone()));
+        
+        // 如果有超类，将super绑定到超类
+        if let Some(super_class) = &super_class {
+            class_env
+                .borrow_mut()
+                .define_local("super", Literal::ClassValue(Rc::clone(super_class)));
+        }
+        
+        // 将方法存入类环境
+        for method in methods {
+            if let Stmt::Function {
+                name: method_name,
                 params,
+                has_rest,
                 body,
-            } => {
-                // 创建闭包环境（继承当前环境）
-                let closure_env = Environment::new(Some(self.environment.clone()));
-                
-                // 预定义函数名（用于递归）
-                closure_env.borrow_mut().define(name.lexeme.clone(), Literal::Nil);
+                is_getter,
+            } = method {
+                let func = LoxFunction {
+                    name: format!("{}.{}", name, method_name.lexeme),
+                    params: params.clone(),
+                    has_rest: *has_rest,
+                    body: Rc::new(body.clone()),
+                    closure: class_env.clone(), // 直接使用 Rc 克隆
+                    is_initializer: method_name.lexeme == "init",
+                    is_getter: *is_getter,
+                };
+                class_env.borrow_mut().define(
+                    method_name.lexeme.clone(),
+                    Literal::FunctionValue(Rc::new(func)),
+                );
+            }
+        }
 
-                // 创建函数对象
+        // static 方法同样以类环境为闭包，但调用时不绑定实例
+        let mut static_methods = HashMap::new();
+        for method in statics {
+            if let Stmt::Function {
+                name: method_name,
+                params,
+                has_rest,
+                body,
+                ..
+            } = method {
                 let func = LoxFunction {
+                    name: format!("{}.{}", name, method_name.lexeme),
                     params: params.clone(),
-                    body: body.clone(),
-                    closure: closure_env.clone(),
+                    has_rest: *has_rest,
+                    body: Rc::new(body.clone()),
+                    closure: class_env.clone(),
                     is_initializer: false,
+                    is_getter: false,
                 };
+                static_methods.insert(method_name.lexeme.clone(), func);
+            }
+        }
 
-                // 更新闭包环境中的函数引用
-                closure_env.borrow_mut().assign(
-                    &Token::new_identifier(name.lexeme.clone()),
-                    Literal::FunctionValue(func.clone())
-                )?;
+        // 创建类对象
+        let class = LoxClass {
+            name: name.to_string(),
+            environment: class_env,
+            statics: static_methods,
+            superclass: super_class,
+            fields: fields.to_vec(),
+        };
 
-                // 将函数绑定到当前环境
-                self.environment.borrow_mut().define(name.lexeme.clone(), Literal::FunctionValue(func));
-                Ok(())
-            }
+        Ok(class)
+    }
 
-            Stmt::Class {
-                name,
-                superclass,
-                methods,
-            } => {
-                // 解析超类
-                let super_class = match superclass {
-                    Some(expr) => {
-                        let val = self.evaluate(expr)?;
-                        match val {
-                            Literal::ClassValue(c) => Some(Box::new(c)),
-                            _ => {
-                                return Err(RuntimeError::Runtime(
-                                    "Superclass must be a class.".into(),
-                                ));
-                            }
-                        }
-                    }
-                    None => None,
-                };
+    fn execute_for(
+        &mut self,
+        initializer: &Option<Box<Stmt>>,
+        condition: &Option<Expr>,
+        increment: &Option<Expr>,
+        body: &Stmt,
+    ) -> Result<()> {
+        // 初始化语句声明的变量只在循环内可见
+        let previous = Rc::clone(&self.environment);
+        self.environment = Environment::new(Some(Rc::clone(&previous)));
+        let result = self.execute_for_loop(initializer, condition, increment, body);
+        self.environment = previous;
+        result
+    }
 
-                // 创建类环境（继承当前环境）
-                let class_env = Environment::new(Some(self.environment.clone()));
-                
-                // 如果有超类，将super绑定到超类
-                if let Some(super_class) = &super_class {
-                    class_env.borrow_mut().define(
-                        "super".to_string(),
-                        Literal::ClassValue((**super_class).clone()),
-                    );
-                }
-                
-                // 将方法存入类环境
-                for method in methods {
-                    if let Stmt::Function {
-                        name: method_name,
-                        params,
-                        body,
-                    } = method {
-                        let func = LoxFunction {
-                            params: params.clone(),
-                            body: body.clone(),
-                            closure: class_env.clone(), // 直接使用 Rc 克隆
-                            is_initializer: method_name.lexeme == "init",
-                        };
-                        class_env.borrow_mut().define(
-                            method_name.lexeme.clone(),
-                            Literal::FunctionValue(func),
-                        );
-                    }
-                }
+    fn execute_for_loop(
+        &mut self,
+        initializer: &Option<Box<Stmt>>,
+        condition: &Option<Expr>,
+        increment: &Option<Expr>,
+        body: &Stmt,
+    ) -> Result<()> {
+        if let Some(init) = initializer {
+            self.execute(init)?;
+        }
 
-                // 创建类对象
-                let class = LoxClass {
-                    name: name.lexeme.clone(),
-                    environment: class_env,
-                    superclass: super_class,
-                };
+        loop {
+            let cond = match condition {
+                Some(c) => self.evaluate(c)?,
+                None => Literal::Boolean(true),
+            };
+            if !self.is_truthy(&cond) {
+                break;
+            }
 
-                self.environment.borrow_mut().define(name.lexeme.clone(), Literal::ClassValue(class));
-                Ok(())
+            // continue 跳过本轮剩余部分，但仍要执行增量表达式
+            match self.execute(body) {
+                Err(RuntimeError::Break) => break,
+                Err(RuntimeError::Continue) => {}
+                other => other?,
             }
 
-            Stmt::Return { keyword: _, value } => {
-                let return_value = match value {
-                    Some(expr) => self.evaluate(expr)?,
-                    None => Literal::Nil,
-                };
-                // 使用自定义错误类型传递返回值
-                Err(RuntimeError::Return(return_value))
+            if let Some(inc) = increment {
+                self.evaluate(inc)?;
             }
         }
+        Ok(())
+    }
+
+    fn execute_for_each(&mut self, name: &Token, iterable: &Expr, body: &Stmt) -> Result<()> {
+        // 先取出全部元素：循环体中修改列表不影响本次遍历
+        let items: Vec<Literal> = match self.evaluate(iterable)? {
+            Literal::ListValue(list) => list.borrow().clone(),
+            Literal::StringValue(s) => s.chars().map(|c| Literal::StringValue(c.to_string().into())).collect(),
+            other => {
+                return Err(error_at(
+                    name,
+                    &format!("Can only iterate over lists and strings but got {}.", other.type_name()),
+                ));
+            }
+        };
+
+        let previous = Rc::clone(&self.environment);
+        for item in items {
+            // 每轮使用新的环境，闭包捕获的是本轮的循环变量
+            self.environment = Environment::new(Some(Rc::clone(&previous)));
+            self.environment.borrow_mut().define_local(&name.lexeme, item);
+            let result = self.execute(body);
+            self.environment = Rc::clone(&previous);
+            match result {
+                Err(RuntimeError::Break) => break,
+                Err(RuntimeError::Continue) => {}
+                other => other?,
+            }
+        }
+        Ok(())
     }
 
     fn execute_block(&mut self, stmts: &[Stmt]) -> Result<()> {
         let previous = Rc::clone(&self.environment);
         self.environment = Environment::new(Some(previous));
-        let result = stmts.iter().try_for_each(|stmt| self.execute(stmt));
+        let result = self.execute_scope(stmts);
         let cur_environment = self.environment.borrow().enclosing.as_ref().unwrap().clone();
         self.environment = cur_environment;
         result
     }
 
 
-    // 调用函数时使用
-    fn call_function(
+    // 在当前（局部）环境中执行一组语句。与解析器一致，先为其中声明的函数名预留槽位
+    fn execute_scope(&mut self, stmts: &[Stmt]) -> Result<()> {
+        for stmt in stmts {
+            if let Stmt::Function { name, .. } = stmt {
+                self.environment.borrow_mut().reserve_local(&name.lexeme);
+            }
+        }
+        stmts.iter().try_for_each(|stmt| self.execute(stmt))
+    }
+
+    // var/fun/class 声明：全局作用域按名字存放，局部作用域存入解析器分配的槽位
+    fn define_variable(&mut self, name: &str, value: Literal) {
+        if Rc::ptr_eq(&self.environment, &self.globals) {
+            self.environment.borrow_mut().define(name.to_string(), value);
+        } else {
+            self.environment.borrow_mut().define_local(name, value);
+        }
+    }
+
+    // 调用函数、类（实例化）或原生函数
+    fn call_value(&mut self, callee: Literal, args: Vec<Literal>, paren: &Token) -> Result<Literal> {
+        match callee {
+            Literal::FunctionValue(func) => self.call_function(&func, args, paren),
+            Literal::ClassValue(cls) => self.call_class_constructor(&cls, args, paren),
+            Literal::NativeFunctionValue(native) => {
+                if let Some(arity) = native.arity {
+                    check_arity(arity, args.len(), paren)?;
+                }
+                let outer = std::mem::replace(&mut self.native_call_line, paren.line);
+                let result = (native.func)(self, &args);
+                self.native_call_line = outer;
+                result
+            }
+            _ => Err(error_at(paren, "Can only call functions and classes.")),
+        }
+    }
+
+    /// 以原生函数的名字注册为全局变量，嵌入时可以提供带状态的闭包
+    pub fn register_native(&mut self, native: NativeFunction) {
+        let name = native.name.to_string();
+        self.globals.borrow_mut().define(name, Literal::NativeFunctionValue(Rc::new(native)));
+    }
+
+    /// 供原生函数回调 Lox 函数、类或其他原生函数，如 map(list, fn)；
+    /// 参数个数不符等错误报告在原生函数的调用处
+    pub fn call(&mut self, callee: &Literal, args: Vec<Literal>) -> Result<Literal> {
+        let paren = Token::new(TokenType::RightParen, self.native_call_line, ")".into(), None);
+        self.call_value(callee.clone(), args, &paren)
+    }
+
+    pub(crate) fn call_function(
+        &mut self,
+        func: &LoxFunction,
+        args: Vec<Literal>,
+        paren: &Token,
+    ) -> Result<Literal> {
+        check_function_arity(func, args.len(), paren)?;
+
+        // 调用层数超过上限时报可捕获的运行时错误，而不是让宿主进程栈溢出崩溃
+        if self.call_depth >= self.max_call_depth {
+            return Err(error_at(paren, "Stack overflow."));
+        }
+        self.call_depth += 1;
+        let result = self.call_function_timed(func, args, paren);
+        self.call_depth -= 1;
+        result
+    }
+
+    fn call_function_timed(
+        &mut self,
+        func: &LoxFunction,
+        args: Vec<Literal>,
+        paren: &Token,
+    ) -> Result<Literal> {
+        // 未开启性能分析时不计时，没有额外开销
+        if self.profile.is_none() {
+            return self.call_function_body(func, args, paren);
+        }
+
+        let entry = self.profile.as_mut().unwrap().entry(func.name.clone()).or_default();
+        entry.calls += 1;
+        entry.active += 1;
+        let start = Instant::now();
+        let result = self.call_function_body(func, args, paren);
+        let elapsed = start.elapsed();
+        let entry = self.profile.as_mut().unwrap().get_mut(&func.name).unwrap();
+        entry.active -= 1;
+        if entry.active == 0 {
+            entry.total += elapsed;
+        }
+        result
+    }
+
+    fn call_function_body(
         &mut self,
         func: &LoxFunction,
         args: Vec<Literal>,
@@ -601,82 +1627,166 @@ impl Interpreter {
     ) -> Result<Literal> {
         let call_env = Environment::new(Some(Rc::clone(&func.closure)));
     
-        // 绑定参数
-        for (param, arg) in func.params.iter().zip(args.iter()) {
-            call_env.borrow_mut().define(param.lexeme.clone(), arg.clone());
+        // 绑定参数；有 ...rest 时，前面的参数按位置绑定，其余实参组成列表绑定到 rest
+        let mut args = args.into_iter();
+        let positional = func.params.len() - usize::from(func.has_rest);
+        for param in &func.params[..positional] {
+            call_env.borrow_mut().define_local(&param.lexeme, args.next().unwrap_or(Literal::Nil));
+        }
+        if let Some(rest) = func.params.get(positional) {
+            let rest_args = Literal::ListValue(Rc::new(RefCell::new(args.collect())));
+            call_env.borrow_mut().define_local(&rest.lexeme, rest_args);
         }
 
-        // 执行函数体
+        // 执行函数体（参数与函数体共用调用环境，与解析器的作用域划分一致）
         let prev_env = Rc::clone(&self.environment);
-        self.environment = Rc::clone(&call_env);
-        let result = self.execute_block(&func.body);
+        self.environment = call_env;
+        let result = self.execute_scope(&func.body);
         self.environment = prev_env;
 
-        if func.is_initializer {
-            Ok(Literal::Nil) // 返回值被call_class_constructor忽略
-        } else {
-            match result {
-                Ok(_) => Ok(Literal::Nil),
-                Err(RuntimeError::Return(value)) => Ok(value),
-                Err(e) => Err(e),
-            }
+        match result {
+            // 初始化方法的返回值被call_class_constructor忽略，但其中的错误要向上传递
+            Ok(_) | Err(RuntimeError::Return(_)) if func.is_initializer => Ok(Literal::Nil),
+            Ok(_) => Ok(Literal::Nil),
+            Err(RuntimeError::Return(value)) => Ok(value),
+            Err(e) => Err(e),
         }
     }
 
     // 新建一个实例时调用
     fn call_class_constructor(
         &mut self,
-        cls: &LoxClass,
+        cls: &Rc<LoxClass>,
         args: Vec<Literal>,
         paren: &Token,
     ) -> Result<Literal> {
         let instance_name = format!("{}#{}", cls.name, self.instance_counter);
         self.instance_counter += 1;
 
-        let instance_env = Rc::new(RefCell::new(Environment {
-            values: HashMap::new(),
-            enclosing: None,
-        }));
-        
-        // 从类环境中复制super绑定到实例环境
-        if let Ok(super_value) = cls.environment.borrow().get(
-            &Token::new_identifier("super".to_string())
-        ) {
-            instance_env.borrow_mut().define(
-                "super".to_string(),
-                super_value
-            );
-        }
-
-        let instance = LoxInstance {
-            class: cls.clone(),
+        // 实例字段按名字存放
+        let instance_env = Environment::new(None);
+
+        let instance = Rc::new(LoxInstance {
+            class: Rc::clone(cls),
             environment: instance_env,
             name: instance_name,
-        };
+        });
 
-        // 自动调用初始化方法
+        // 先初始化字段声明，再调用 init
+        self.init_fields(cls, &instance)?;
+
+        // 自动调用初始化方法（由 call_function 检查参数个数），没有 init 时不接受参数
         if let Some(Literal::FunctionValue(init)) = cls.find_method("init") {
             let bound_init = init.bind(&instance);
             self.call_function(&bound_init, args, paren)?;
+        } else {
+            check_arity(0, args.len(), paren)?;
         }
         Ok(Literal::InstanceValue(instance))
     }
 
-    pub fn debug_print_env(&self) {
-        self.environment.borrow().debug_print(0);
+    // 按继承链从基类到子类依次求值字段初始化表达式，求值时 this 指向新实例
+    fn init_fields(&mut self, cls: &LoxClass, instance: &Rc<LoxInstance>) -> Result<()> {
+        if let Some(superclass) = &cls.superclass {
+            self.init_fields(superclass, instance)?;
+        }
+        if cls.fields.is_empty() {
+            return Ok(());
+        }
+
+        let previous = Rc::clone(&self.environment);
+        self.environment = Environment::new(Some(Rc::clone(&cls.environment)));
+        self.environment
+            .borrow_mut()
+            .define_local("this", Literal::InstanceValue(Rc::clone(instance)));
+
+        let result = cls.fields.iter().try_for_each(|field| {
+            if let Stmt::VarDecl { name, initializer } = field {
+                let value = match initializer {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Literal::Nil,
+                };
+                instance.environment.borrow_mut().define(name.lexeme.clone(), value);
+            }
+            Ok(())
+        });
+
+        self.environment = previous;
+        result
+    }
+
+    /// breakpoint() 的实现：未开启交互调试时直接返回
+    #[cfg(not(feature = "no-std"))]
+    pub(crate) fn debug_break(&mut self) -> Result<()> {
+        if !self.interactive_debug {
+            return Ok(());
+        }
+        let previous = self.in_debugger;
+        self.in_debugger = true;
+        let result = self.debug_repl();
+        self.in_debugger = previous;
+        result
+    }
+
+    // 调试 REPL：continue（或 c、输入结束）恢复执行，vars 列出当前的局部变量，
+    // 其余输入在当前环境中执行，不以 ';' 或 '}' 结尾的输入视为表达式并打印其值
+    #[cfg(not(feature = "no-std"))]
+    fn debug_repl(&mut self) -> Result<()> {
+        use std::io::BufRead;
+
+        let stdin = io::stdin();
+        loop {
+            write!(self.output, "debug> ").map_err(output_error)?;
+            self.output.flush().map_err(output_error)?;
+
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) | Err(_) => return Ok(()),
+                Ok(_) => {}
+            }
+            match line.trim() {
+                "" => {}
+                "continue" | "c" => return Ok(()),
+                "vars" => self.print_locals()?,
+                input => {
+                    let source = if input.ends_with(';') || input.ends_with('}') {
+                        input.to_string()
+                    } else {
+                        format!("print {};", input)
+                    };
+                    let result = crate::parse(&source)
+                        .map_err(UnifiedError::Parse)
+                        .and_then(|stmts| self.interpret(&stmts).map_err(UnifiedError::Runtime));
+                    if let Err(e) = result {
+                        writeln!(self.output, "{}", e).map_err(output_error)?;
+                    }
+                }
+            }
+        }
     }
 
-    fn stringify(&self, value: Literal) -> String {
-        match value {
-            Literal::Nil => "nil".into(),
-            Literal::Boolean(b) => b.to_string(),
-            Literal::NumberValue(n) => format!("{}", n),
-            Literal::StringValue(s) => s,
-            Literal::FunctionValue(_) => "call fn".into(),
-            Literal::ClassValue(c) => format!("<class {}>", c.name),
-            Literal::InstanceValue(i) => format!("<instance of {}>", i.class.name),
-            Literal::None => "nil".into(), // 合并None和Nil处理
-            Literal::NativeFunctionValue(_) => "call native fn".into(),
+    // 由内向外列出全局环境之前各层作用域中的变量
+    #[cfg(not(feature = "no-std"))]
+    fn print_locals(&mut self) -> Result<()> {
+        let mut lines = Vec::new();
+        let mut env = Rc::clone(&self.environment);
+        while !Rc::ptr_eq(&env, &self.globals) {
+            for (name, value) in env.borrow().iter() {
+                lines.push(format!("{} = {}", name, stringify(value)));
+            }
+            let enclosing = env.borrow().enclosing.clone();
+            match enclosing {
+                Some(outer) => env = outer,
+                None => break,
+            }
         }
+        for line in lines {
+            writeln!(self.output, "{}", line).map_err(output_error)?;
+        }
+        Ok(())
+    }
+
+    pub fn debug_print_env(&self) {
+        self.environment.borrow().debug_print(0);
     }
 }
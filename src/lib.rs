@@ -1,3 +1,6 @@
+// 错误类型中携带 Token/Literal，体积较大但便于报告，统一放行
+#![allow(clippy::result_large_err)]
+
 pub mod scanner;
 pub mod syntaxer;
 pub mod expr;
@@ -5,6 +8,106 @@ pub mod statement;
 pub mod token;
 pub mod interpreter;
 pub mod environment;
+pub mod native;
+pub mod resolver;
+
+use std::error::Error;
+use std::fmt;
+
+use environment::RuntimeError;
+use scanner::Scanner;
+use statement::Stmt;
+use syntaxer::{ParseError, Parser};
+use token::TokenType;
+
+/// 词法、语法、运行三个阶段的错误统一类型
+#[derive(Debug)]
+pub enum UnifiedError {
+    Scan(Vec<String>),
+    Parse(Vec<ParseError>),
+    Runtime(RuntimeError),
+}
+
+impl fmt::Display for UnifiedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnifiedError::Scan(errs) => write!(f, "{}", errs.join("\n")),
+            UnifiedError::Parse(errs) => {
+                let lines: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            UnifiedError::Runtime(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for UnifiedError {}
+
+impl From<Vec<ParseError>> for UnifiedError {
+    fn from(errs: Vec<ParseError>) -> Self {
+        UnifiedError::Parse(errs)
+    }
+}
+
+impl From<RuntimeError> for UnifiedError {
+    fn from(e: RuntimeError) -> Self {
+        UnifiedError::Runtime(e)
+    }
+}
+
+/// 只做词法和语法分析，返回 AST 而不执行，供格式化、lint 等工具使用。
+///
+/// 出错时返回的是全部错误而不只是第一个：词法错误（以 `TokenType::Error`
+/// 的 Token 表示）和语法错误合并后按行号和列号排列。
+pub fn parse(source: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    parse_with(source, Parser::parse)
+}
+
+/// 与 [`parse`] 相同，但源码末尾的表达式可以省略分号（REPL 与 `-e` 的写法）。
+pub fn parse_repl(source: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    parse_with(source, Parser::parse_repl)
+}
+
+fn parse_with(
+    source: &str,
+    parse: fn(&mut Parser) -> Result<Vec<Stmt>, Vec<ParseError>>,
+) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    let mut scanner = Scanner::new(source);
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    loop {
+        let token = scanner.scan_token();
+        match token.token_type {
+            TokenType::Error => errors.push(ParseError {
+                message: token.lexeme.clone(),
+                token,
+                expected: None,
+                found: None,
+            }),
+            TokenType::Eof => {
+                tokens.push(token);
+                break;
+            }
+            _ => tokens.push(token),
+        }
+    }
+
+    match parse(&mut Parser::new(tokens)) {
+        Ok(statements) if errors.is_empty() => Ok(statements),
+        Ok(_) => Err(errors),
+        Err(parse_errors) => {
+            // 词法错误在扫描时先收集，与语法错误合并后按位置排序（稳定排序，同一位置保持原顺序）
+            errors.extend(parse_errors);
+            errors.sort_by_key(|error| (error.token.line, error.token.column));
+            Err(errors)
+        }
+    }
+}
+
+/// 无状态地运行一段源码：每次调用都使用全新的解释器
+pub fn run(source: &str) -> Result<(), UnifiedError> {
+    interpreter::Interpreter::new().interpret_source(source)
+}
 
 #[macro_export]
 macro_rules! assert_token {
@@ -40,7 +143,7 @@ macro_rules! assert_token {
             (expected_str, Some(Literal::StringValue(actual_str))) => {
                 let expected = expected_str.to_string();
                 assert_eq!(
-                    actual_str, &expected,
+                    &**actual_str, expected.as_str(),
                     "String literal mismatch.\nExpected: {:?}\nGot: {:?}",
                     expected, actual_str
                 );
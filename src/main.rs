@@ -1,20 +1,202 @@
 use clap::Parser; 
 use std::fs;
-// use std::path::Path;
+use std::io::{self, BufRead, Write};
 
 use lox::scanner::Scanner;
-use lox::syntaxer::Parser as SyntaxParser; // 重命名语法分析器
-use lox::interpreter::Interpreter;
+use lox::statement::Stmt;
+use lox::syntaxer::{render_parse_errors, ParseError, Parser as SyntaxParser}; // 重命名语法分析器
+use lox::token::Token;
+use lox::environment::RuntimeError;
+use lox::interpreter::{stringify, ComparisonMode, Interpreter, DEFAULT_MAX_CALL_DEPTH};
+use lox::resolver::Resolver;
 use std::error::Error;
 
 #[derive(clap::Parser)] // 明确指定使用 clap 的宏
 #[command(author, version, about)]
 struct Args {
-    // Input Lox file path 
-    input: String,
+    // Input Lox file path（省略时进入交互式 REPL）
+    input: Option<String>,
+
+    /// 直接执行命令行给出的代码；末尾的表达式可省略分号，并像 REPL 一样输出其值
+    #[arg(short = 'e', long = "eval", value_name = "CODE", conflicts_with = "input")]
+    eval: Option<String>,
+
+    /// 只做词法、语法和变量解析检查，不执行程序（无错误退出码 0，有错误退出码 65）
+    #[arg(long)]
+    check: bool,
+
+    /// 比较运算时允许数字与字符串互相比较（字符串先转为数字）
+    #[arg(long)]
+    loose_comparison: bool,
+
+    /// 字符串与数字/布尔相加时自动转换为字符串拼接
+    #[arg(long)]
+    string_plus_coerces: bool,
+
+    /// 执行完顶层代码后，若定义了 main() 则调用它
+    #[arg(long)]
+    call_main: bool,
+
+    /// 同 --call-main，但没有定义 main() 时报错
+    #[arg(long)]
+    require_main: bool,
+
+    /// 开启 breakpoint()：执行到断点时暂停并进入调试 REPL
+    #[arg(long)]
+    interactive_debug: bool,
+
+    /// 确定性模式：sleep() 不实际等待，随机数从固定种子开始
+    #[arg(long)]
+    deterministic: bool,
+
+    /// 只做词法分析，逐行输出 token（行号 | 类型 | 词素 | 字面量）后退出，不解析也不执行
+    #[arg(long)]
+    tokens: bool,
+
+    /// 只做语法分析，以 JSON 输出语法树后退出，不执行
+    #[arg(long)]
+    ast: bool,
+
+    /// --tokens 的输出格式
+    #[arg(long, value_enum, default_value_t = TokenFormat::Text, requires = "tokens")]
+    format: TokenFormat,
+
+    /// 程序结束时向 stderr 输出每个函数的调用次数和累计耗时
+    #[arg(long)]
+    profile: bool,
+
+    /// 函数调用层数上限，超过时报 Stack overflow. 运行时错误；大于 8192 时按解释器线程的栈所能容纳的 8192 层处理
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_CALL_DEPTH)]
+    max_call_depth: usize,
+
+    /// 运行时拼接出的字符串最大字节数，超出时报运行时错误
+    #[arg(long, value_name = "BYTES")]
+    max_string_length: Option<usize>,
+
+    /// 文件之后的参数，以字符串列表传给 main(args)
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, requires = "input")]
+    script_args: Vec<String>,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum TokenFormat {
+    Text,
+    Json,
+}
+
+// 静态检查失败时的退出码
+const EXIT_CHECK_FAILED: i32 = 65;
+// 未捕获的 throw 的退出码
+const EXIT_UNCAUGHT_THROW: i32 = 70;
+
+// -e 的代码与 REPL 使用同一解析入口，文件则要求每条语句都以分号结尾
+fn parse_tokens(tokens: Vec<Token>, eval: bool) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    let mut parser = SyntaxParser::new(tokens);
+    if eval { parser.parse_repl() } else { parser.parse() }
+}
+
+/// --check 模式：报告全部诊断信息，不解释执行
+fn check_source(code: &str, eval: bool) -> i32 {
+    // 词法与语法错误合并后一起报告，都没有时再做变量解析
+    let parsed = if eval { lox::parse_repl(code) } else { lox::parse(code) };
+    match parsed.and_then(|statements| Resolver::new().resolve(&statements)) {
+        Ok(_) => 0,
+        Err(errs) => {
+            println!("{}", render_parse_errors(&errs));
+            EXIT_CHECK_FAILED
+        }
+    }
+}
+
+/// --tokens 模式：输出词法分析结果，调试语法时使用
+fn print_tokens(tokens: &[Token], format: TokenFormat) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match format {
+        TokenFormat::Json => println!("{}", serde_json::to_string_pretty(tokens)?),
+        TokenFormat::Text => {
+            for token in tokens {
+                let literal = token.literal.as_ref().map(stringify).unwrap_or_default();
+                let line = format!("{} | {:?} | {} | {}", token.line, token.token_type, token.lexeme, literal);
+                println!("{}", line.trim_end());
+            }
+        }
+    }
+    Ok(())
+}
+
+// 按命令行参数配置解释器
+fn configured_interpreter(args: &Args) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    if args.loose_comparison {
+        interpreter.set_comparison_mode(ComparisonMode::Loose);
+    }
+    interpreter.set_string_plus_coerces(args.string_plus_coerces);
+    interpreter.set_max_string_length(args.max_string_length);
+    interpreter.set_interactive_debug(args.interactive_debug);
+    interpreter.set_profiling(args.profile);
+    interpreter.set_deterministic(args.deterministic);
+    interpreter.set_max_call_depth(args.max_call_depth.min(MAX_CALL_DEPTH_LIMIT));
+    interpreter
+}
+
+/// 交互式 REPL：逐行执行输入，顶层表达式回显其值，出错后继续读取下一行
+fn run_repl(interpreter: &mut Interpreter) -> Result<(), Box<dyn Error + Send + Sync>> {
+    interpreter.set_repl_mode(true);
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        if let Err(e) = interpreter.interpret_source(&line) {
+            println!("{}", e);
+        }
+    }
+}
+
+// --profile 的统计表输出到 stderr，不与程序输出混在一起
+fn print_profile(interpreter: &Interpreter) {
+    if let Some(report) = interpreter.profile_report() {
+        eprint!("{}", report);
+    }
+}
+
+// 运行时错误输出到 stdout 并以退出码 1 结束；未捕获的 throw 输出到 stderr，退出码 70
+fn exit_on_runtime_error(interpreter: &Interpreter, error: RuntimeError) -> ! {
+    // 使用 Display 格式输出错误
+    let code = if let RuntimeError::UserThrow(_) = error {
+        eprintln!("{}", error);
+        EXIT_UNCAUGHT_THROW
+    } else {
+        println!("{}", error);
+        1
+    };
+    print_profile(interpreter);
+    std::process::exit(code);
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+// 解释器在独立线程上运行所用的栈大小。树遍历解释器每层 Lox 调用占用多个 Rust 栈帧，
+// 栈要足够大，使调用层数上限先于真正的栈溢出生效
+const INTERPRETER_STACK_SIZE: usize = 512 * 1024 * 1024;
+
+// 为每层 Lox 调用预留的宿主栈。实测调试构建每层约 6~11KB，函数体内嵌套较深的表达式
+// 会占用更多，这里留出数倍余量
+const STACK_PER_CALL: usize = 64 * 1024;
+
+// --max-call-depth 的实际上限：解释器线程的栈能容纳的调用层数（8192）
+const MAX_CALL_DEPTH_LIMIT: usize = INTERPRETER_STACK_SIZE / STACK_PER_CALL;
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let interpreter_thread = std::thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(run)?;
+    // 线程内的 panic 由 panic hook 处理并直接退出进程
+    interpreter_thread.join().unwrap_or(Ok(()))
+}
+
+fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
     // 添加 panic hook 确保错误信息正确格式化
     std::panic::set_hook(Box::new(|panic_info| {
         if let Some(s) = panic_info.payload().downcast_ref::<String>() {
@@ -27,41 +209,68 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let args = Args::parse();
 
-    let code = fs::read_to_string(&args.input)?;
+    let eval = args.eval.is_some();
+    let code = match (&args.eval, &args.input) {
+        (Some(code), _) => code.clone(),
+        (None, Some(input)) => fs::read_to_string(input)?,
+        (None, None) => {
+            if args.check {
+                println!("--check requires an input file.");
+                std::process::exit(EXIT_CHECK_FAILED);
+            }
+            let mut my_interpreter = configured_interpreter(&args);
+            return run_repl(&mut my_interpreter);
+        }
+    };
+    if args.check {
+        std::process::exit(check_source(&code, eval));
+    }
+
     let mut scanner = Scanner::new(&code);
     
     let tokens = scanner.scan_tokens().map_err(|errs| {
-        let first_err = errs.first().unwrap();
-        println!("{}", first_err);
+        // 报告全部词法错误，而不只是第一个
+        for err in errs {
+            println!("{}", err);
+        }
         std::process::exit(1);
     })?;
 
-    // 保存词法分析结果
-    // let lex_path = Path::new("output").join("lex_result.json");
-    // fs::write(lex_path, serde_json::to_string_pretty(&tokens)?)?;
-    // println!("[DEBUG] finish lexeme scanner.");
+    if args.tokens {
+        return print_tokens(&tokens, args.format);
+    }
 
     // 语法分析错误处理
-    let mut parser = SyntaxParser::new(tokens);
-    let ast = parser.parse().map_err(|e| {
-        // 使用 Display 格式输出错误
-        println!("{}", e);
+    let ast = parse_tokens(tokens, eval).map_err(|errs| {
+        // 报告全部语法错误（带行号），一次编译即可修正多处错误
+        println!("{}", render_parse_errors(&errs));
         std::process::exit(1);
     })?;
 
-    // 保存语法树
-    // let ast_path = Path::new("output").join("ast_result.json");
-    // fs::write(ast_path, serde_json::to_string_pretty(&ast)?)?;
-    // println!("[DEBUG] finish parser.");
+    if args.ast {
+        println!("{}", serde_json::to_string_pretty(&ast)?);
+        return Ok(());
+    }
 
-    // 解释执行错误处理
-    let mut my_interpreter = Interpreter::new();
-    my_interpreter.interpret(&ast).map_err(|e| {
-        // 使用 Display 格式输出错误
-        println!("{}", e);
+    // 变量解析：在执行前确定每个局部变量引用的作用域深度
+    let mut my_interpreter = configured_interpreter(&args);
+    my_interpreter.resolve(&ast).map_err(|errs| {
+        println!("{}", render_parse_errors(&errs));
         std::process::exit(1);
     })?;
 
+    // 解释执行错误处理
+    my_interpreter
+        .interpret(&ast)
+        .map_err(|e| exit_on_runtime_error(&my_interpreter, e))?;
+
+    if args.call_main || args.require_main {
+        my_interpreter
+            .call_main(args.require_main, &args.script_args)
+            .map_err(|e| exit_on_runtime_error(&my_interpreter, e))?;
+    }
+    print_profile(&my_interpreter);
+
     // println!("[DEBUG] finish interpreter.");
     Ok(())
 }
\ No newline at end of file
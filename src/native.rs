@@ -0,0 +1,548 @@
+use crate::environment::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::token::{format_number, Literal};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+type Result<T> = std::result::Result<T, RuntimeError>;
+
+// 参数个数固定的原生函数在注册时声明 arity，由解释器在调用前统一检查（与用户函数相同），
+// 下面的实现可以直接按下标取参数
+
+// 取出字符串参数，类型不符时报告函数名和实际类型
+fn expect_string<'a>(args: &'a [Literal], index: usize, func: &str) -> Result<&'a str> {
+    args[index].as_string().ok_or_else(|| {
+        RuntimeError::Runtime(
+            format!("{}() expects a string but got {}.", func, args[index].type_name()),
+        )
+    })
+}
+
+// 取出列表参数，类型不符时报告函数名和实际类型
+fn expect_list<'a>(args: &'a [Literal], index: usize, func: &str) -> Result<&'a Rc<RefCell<Vec<Literal>>>> {
+    match &args[index] {
+        Literal::ListValue(list) => Ok(list),
+        other => Err(RuntimeError::Runtime(
+            format!("{}() expects a list but got {}.", func, other.type_name()),
+        )),
+    }
+}
+
+// 取出字典参数
+fn expect_map<'a>(args: &'a [Literal], index: usize, func: &str) -> Result<&'a Rc<RefCell<HashMap<String, Literal>>>> {
+    match &args[index] {
+        Literal::MapValue(map) => Ok(map),
+        other => Err(RuntimeError::Runtime(
+            format!("{}() expects a map but got {}.", func, other.type_name()),
+        )),
+    }
+}
+
+// 取出字符下标参数：必须是非负整数
+fn expect_index(args: &[Literal], index: usize, func: &str) -> Result<usize> {
+    match &args[index] {
+        Literal::NumberValue(n) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+        Literal::NumberValue(n) => Err(RuntimeError::Runtime(
+            format!("{}() expects a non-negative integer index but got {}.", func, format_number(*n)),
+        )),
+        other => Err(RuntimeError::Runtime(
+            format!("{}() expects a number but got {}.", func, other.type_name()),
+        )),
+    }
+}
+
+// 取出数字参数
+fn expect_number(args: &[Literal], index: usize, func: &str) -> Result<f64> {
+    match &args[index] {
+        Literal::NumberValue(n) => Ok(*n),
+        other => Err(RuntimeError::Runtime(
+            format!("{}() expects a number but got {}.", func, other.type_name()),
+        )),
+    }
+}
+
+/// contains(collection, value)：字符串判断是否包含子串，列表判断是否有与 value 相等（==）的元素，
+/// 字典判断是否有键 value
+pub fn contains(args: &[Literal]) -> Result<Literal> {
+    match (&args[0], &args[1]) {
+        (Literal::MapValue(map), Literal::StringValue(key)) => {
+            Ok(Literal::Boolean(map.borrow().contains_key(&**key)))
+        }
+        (Literal::MapValue(_), other) => Err(RuntimeError::Runtime(
+            format!("contains() on a map expects a string key but got {}.", other.type_name()),
+        )),
+        (Literal::ListValue(list), value) => {
+            Ok(Literal::Boolean(list.borrow().iter().any(|e| e == value)))
+        }
+        (Literal::StringValue(s), Literal::StringValue(sub)) => {
+            Ok(Literal::Boolean(s.contains(&**sub)))
+        }
+        (Literal::StringValue(_), other) => Err(RuntimeError::Runtime(
+            format!("contains() on a string expects a string but got {}.", other.type_name()),
+        )),
+        (other, _) => Err(RuntimeError::Runtime(
+            format!("contains() does not support {}.", other.type_name()),
+        )),
+    }
+}
+
+/// starts_with(s, prefix)
+pub fn starts_with(args: &[Literal]) -> Result<Literal> {
+    let s = expect_string(args, 0, "starts_with")?;
+    let prefix = expect_string(args, 1, "starts_with")?;
+    Ok(Literal::Boolean(s.starts_with(prefix)))
+}
+
+/// ends_with(s, suffix)
+pub fn ends_with(args: &[Literal]) -> Result<Literal> {
+    let s = expect_string(args, 0, "ends_with")?;
+    let suffix = expect_string(args, 1, "ends_with")?;
+    Ok(Literal::Boolean(s.ends_with(suffix)))
+}
+
+/// trim(s)：去掉首尾空白
+pub fn trim(args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::StringValue(expect_string(args, 0, "trim")?.trim().into()))
+}
+
+/// trim_start(s)：去掉开头空白
+pub fn trim_start(args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::StringValue(expect_string(args, 0, "trim_start")?.trim_start().into()))
+}
+
+/// trim_end(s)：去掉结尾空白
+pub fn trim_end(args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::StringValue(expect_string(args, 0, "trim_end")?.trim_end().into()))
+}
+
+// 类型谓词的公共部分：对唯一的参数求值谓词并返回布尔结果（参数个数由注册时的 arity 检查）
+fn type_predicate(args: &[Literal], test: fn(&Literal) -> bool) -> Result<Literal> {
+    Ok(Literal::Boolean(test(&args[0])))
+}
+
+pub fn is_number(args: &[Literal]) -> Result<Literal> {
+    type_predicate(args, |v| matches!(v, Literal::NumberValue(_)))
+}
+
+pub fn is_string(args: &[Literal]) -> Result<Literal> {
+    type_predicate(args, |v| matches!(v, Literal::StringValue(_)))
+}
+
+pub fn is_bool(args: &[Literal]) -> Result<Literal> {
+    type_predicate(args, |v| matches!(v, Literal::Boolean(_)))
+}
+
+pub fn is_nil(args: &[Literal]) -> Result<Literal> {
+    type_predicate(args, |v| matches!(v, Literal::Nil | Literal::None))
+}
+
+/// 用户函数和原生函数都算作函数
+pub fn is_function(args: &[Literal]) -> Result<Literal> {
+    type_predicate(args, |v| {
+        matches!(
+            v,
+            Literal::FunctionValue(_) | Literal::NativeFunctionValue(_)
+        )
+    })
+}
+
+pub fn is_class(args: &[Literal]) -> Result<Literal> {
+    type_predicate(args, |v| matches!(v, Literal::ClassValue(_)))
+}
+
+pub fn is_instance(args: &[Literal]) -> Result<Literal> {
+    type_predicate(args, |v| matches!(v, Literal::InstanceValue(_)))
+}
+
+pub fn is_list(args: &[Literal]) -> Result<Literal> {
+    type_predicate(args, |v| matches!(v, Literal::ListValue(_)))
+}
+
+pub fn is_map(args: &[Literal]) -> Result<Literal> {
+    type_predicate(args, |v| matches!(v, Literal::MapValue(_)))
+}
+
+/// assert(cond, message?)：cond 为假时报运行时错误。
+/// message 可以是字符串等值，也可以是无参函数——后者只在断言失败时才调用，
+/// 避免断言通过时白白构造消息
+pub fn assert(interpreter: &mut Interpreter, args: &[Literal]) -> Result<Literal> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(RuntimeError::Runtime(
+            format!("Expected 1 or 2 arguments but got {}.", args.len()),
+        ));
+    }
+    if interpreter.is_truthy(&args[0]) {
+        return Ok(Literal::Nil);
+    }
+
+    let message = match args.get(1) {
+        None => return Err(RuntimeError::Runtime("Assertion failed.".into())),
+        Some(Literal::FunctionValue(func)) => {
+            if func.params.len() > usize::from(func.has_rest) {
+                return Err(RuntimeError::Runtime(
+                    "assert() message function must take no arguments.".into(),
+                ));
+            }
+            interpreter.call(&Literal::FunctionValue(func.clone()), Vec::new())?
+        }
+        Some(other) => other.clone(),
+    };
+    Err(RuntimeError::Runtime(format!("Assertion failed: {}", message)))
+}
+
+/// len(x)：列表的元素个数，或字符串的字符数
+pub fn len(args: &[Literal]) -> Result<Literal> {
+    match &args[0] {
+        Literal::ListValue(list) => Ok(Literal::NumberValue(list.borrow().len() as f64)),
+        Literal::StringValue(s) => Ok(Literal::NumberValue(s.chars().count() as f64)),
+        other => Err(RuntimeError::Runtime(
+            format!("len() expects a list or string but got {}.", other.type_name()),
+        )),
+    }
+}
+
+// 以下字符串函数的下标都按 Unicode 标量值（char）计算，而不是字节
+
+/// substring(s, start, end)：下标在 [start, end) 内的字符
+pub fn substring(args: &[Literal]) -> Result<Literal> {
+    let s = expect_string(args, 0, "substring")?;
+    let start = expect_index(args, 1, "substring")?;
+    let end = expect_index(args, 2, "substring")?;
+    let len = s.chars().count();
+    if start > end || end > len {
+        return Err(RuntimeError::Runtime(format!(
+            "substring() range {}..{} out of bounds for length {}.",
+            start, end, len
+        )));
+    }
+    let sub: String = s.chars().skip(start).take(end - start).collect();
+    Ok(Literal::StringValue(sub.into()))
+}
+
+/// indexOf(s, needle)：needle 第一次出现的位置，不存在时返回 -1
+pub fn index_of(args: &[Literal]) -> Result<Literal> {
+    let s = expect_string(args, 0, "indexOf")?;
+    let needle = expect_string(args, 1, "indexOf")?;
+    let index = match s.find(needle) {
+        Some(byte) => s[..byte].chars().count() as f64,
+        None => -1.0,
+    };
+    Ok(Literal::NumberValue(index))
+}
+
+/// charAt(s, i)：与 s[i] 相同
+pub fn char_at(args: &[Literal]) -> Result<Literal> {
+    let s = expect_string(args, 0, "charAt")?;
+    let i = expect_index(args, 1, "charAt")?;
+    s.chars()
+        .nth(i)
+        .map(|c| Literal::StringValue(c.to_string().into()))
+        .ok_or_else(|| {
+            RuntimeError::Runtime(format!(
+                "charAt() index {} out of range for length {}.",
+                i,
+                s.chars().count()
+            ))
+        })
+}
+
+/// to_chars(s)：字符串拆成单个字符组成的列表
+pub fn to_chars(args: &[Literal]) -> Result<Literal> {
+    let chars = expect_string(args, 0, "to_chars")?
+        .chars()
+        .map(|c| Literal::StringValue(c.to_string().into()))
+        .collect();
+    Ok(Literal::ListValue(Rc::new(RefCell::new(chars))))
+}
+
+/// from_chars(list)：把单字符字符串组成的列表拼回字符串
+pub fn from_chars(args: &[Literal]) -> Result<Literal> {
+    let list = expect_list(args, 0, "from_chars")?.borrow();
+    let mut joined = String::with_capacity(list.len());
+    for item in list.iter() {
+        let Some(s) = item.as_string() else {
+            return Err(RuntimeError::Runtime(format!(
+                "from_chars() expects single-character strings but got {}.",
+                item.type_name()
+            )));
+        };
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => joined.push(c),
+            _ => {
+                return Err(RuntimeError::Runtime(format!(
+                    "from_chars() expects single-character strings but got \"{}\".",
+                    s
+                )));
+            }
+        }
+    }
+    Ok(Literal::StringValue(joined.into()))
+}
+
+/// push(list, value)：在列表末尾追加元素
+pub fn push(args: &[Literal]) -> Result<Literal> {
+    expect_list(args, 0, "push")?.borrow_mut().push(args[1].clone());
+    Ok(Literal::Nil)
+}
+
+/// pop(list)：移除并返回列表的最后一个元素
+pub fn pop(args: &[Literal]) -> Result<Literal> {
+    expect_list(args, 0, "pop")?
+        .borrow_mut()
+        .pop()
+        .ok_or_else(|| RuntimeError::Runtime("pop() on an empty list.".into()))
+}
+
+/// enumerate(list)：[下标, 元素] 组成的新列表，便于在 for-in 中同时取得下标
+pub fn enumerate(args: &[Literal]) -> Result<Literal> {
+    let pairs = expect_list(args, 0, "enumerate")?
+        .borrow()
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let pair = vec![Literal::NumberValue(index as f64), item.clone()];
+            Literal::ListValue(Rc::new(RefCell::new(pair)))
+        })
+        .collect();
+    Ok(Literal::ListValue(Rc::new(RefCell::new(pairs))))
+}
+
+/// map(list, fn)：对每个元素调用 fn，返回由结果组成的新列表
+pub fn map(interpreter: &mut Interpreter, args: &[Literal]) -> Result<Literal> {
+    // 先复制元素，回调中修改原列表不影响本次遍历
+    let items = expect_list(args, 0, "map")?.borrow().clone();
+    let mut mapped = Vec::with_capacity(items.len());
+    for item in items {
+        mapped.push(interpreter.call(&args[1], vec![item])?);
+    }
+    Ok(Literal::ListValue(Rc::new(RefCell::new(mapped))))
+}
+
+/// filter(list, fn)：保留 fn 返回真值的元素，返回新列表
+pub fn filter(interpreter: &mut Interpreter, args: &[Literal]) -> Result<Literal> {
+    let items = expect_list(args, 0, "filter")?.borrow().clone();
+    let mut kept = Vec::new();
+    for item in items {
+        let keep = interpreter.call(&args[1], vec![item.clone()])?;
+        if interpreter.is_truthy(&keep) {
+            kept.push(item);
+        }
+    }
+    Ok(Literal::ListValue(Rc::new(RefCell::new(kept))))
+}
+
+/// find(list, fn)：第一个使 fn 返回真值的元素，找到后不再调用 fn；都不满足时返回 nil
+pub fn find(interpreter: &mut Interpreter, args: &[Literal]) -> Result<Literal> {
+    Ok(find_first(interpreter, args, "find", true)?.unwrap_or(Literal::Nil))
+}
+
+/// any(list, fn)：是否有元素使 fn 返回真值，遇到第一个即返回 true；空列表为 false
+pub fn any(interpreter: &mut Interpreter, args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::Boolean(find_first(interpreter, args, "any", true)?.is_some()))
+}
+
+/// all(list, fn)：是否所有元素都使 fn 返回真值，遇到第一个假值即返回 false；空列表为 true
+pub fn all(interpreter: &mut Interpreter, args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::Boolean(find_first(interpreter, args, "all", false)?.is_none()))
+}
+
+// 第一个使 fn 的结果真假性等于 wanted 的元素，找到即停止调用 fn
+fn find_first(interpreter: &mut Interpreter, args: &[Literal], func: &str, wanted: bool) -> Result<Option<Literal>> {
+    // 先复制元素，回调中修改原列表不影响本次遍历
+    let items = expect_list(args, 0, func)?.borrow().clone();
+    for item in items {
+        let result = interpreter.call(&args[1], vec![item.clone()])?;
+        if interpreter.is_truthy(&result) == wanted {
+            return Ok(Some(item));
+        }
+    }
+    Ok(None)
+}
+
+/// keys(map)：按字典序排列的全部键
+pub fn keys(args: &[Literal]) -> Result<Literal> {
+    let mut keys: Vec<String> = expect_map(args, 0, "keys")?.borrow().keys().cloned().collect();
+    keys.sort();
+    let keys = keys.into_iter().map(|k| Literal::StringValue(k.into())).collect();
+    Ok(Literal::ListValue(Rc::new(RefCell::new(keys))))
+}
+
+/// has(map, key)：字典中是否存在该键
+pub fn has(args: &[Literal]) -> Result<Literal> {
+    let map = expect_map(args, 0, "has")?;
+    let key = expect_string(args, 1, "has")?;
+    Ok(Literal::Boolean(map.borrow().contains_key(key)))
+}
+
+/// num(x)：字符串（去掉首尾空白）解析为数字，数字原样返回，布尔值转为 1/0。
+/// 只接受有限的数字："inf"、"NaN" 以及溢出为无穷大的 "1e400" 都报错
+pub fn num(args: &[Literal]) -> Result<Literal> {
+    match &args[0] {
+        Literal::NumberValue(n) => Ok(Literal::NumberValue(*n)),
+        Literal::Boolean(b) => Ok(Literal::NumberValue(if *b { 1.0 } else { 0.0 })),
+        Literal::StringValue(s) => match s.trim().parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Literal::NumberValue(n)),
+            _ => Err(RuntimeError::Runtime(format!("num() cannot convert '{}' to a number.", s))),
+        },
+        other => Err(RuntimeError::Runtime(
+            format!("num() cannot convert {} to a number.", other.type_name()),
+        )),
+    }
+}
+
+/// str(x)：任意值转为字符串，与 print 的显示形式相同
+pub fn str(args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::StringValue(args[0].to_string().into()))
+}
+
+/// sqrt(x)：平方根，x 为负数时报错而不是返回 NaN
+pub fn sqrt(args: &[Literal]) -> Result<Literal> {
+    let x = expect_number(args, 0, "sqrt")?;
+    if x < 0.0 {
+        return Err(RuntimeError::Runtime(
+            format!("sqrt() of negative number {}.", format_number(x)),
+        ));
+    }
+    Ok(Literal::NumberValue(x.sqrt()))
+}
+
+/// pow(b, e)：b 的 e 次幂
+pub fn pow(args: &[Literal]) -> Result<Literal> {
+    let base = expect_number(args, 0, "pow")?;
+    let exponent = expect_number(args, 1, "pow")?;
+    Ok(Literal::NumberValue(base.powf(exponent)))
+}
+
+/// abs(x)：绝对值
+pub fn abs(args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::NumberValue(expect_number(args, 0, "abs")?.abs()))
+}
+
+/// floor(x)：不大于 x 的最大整数
+pub fn floor(args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::NumberValue(expect_number(args, 0, "floor")?.floor()))
+}
+
+/// ceil(x)：不小于 x 的最小整数
+pub fn ceil(args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::NumberValue(expect_number(args, 0, "ceil")?.ceil()))
+}
+
+/// round(x)：四舍五入到整数，恰好在中间时远离 0（round(-2.5) 为 -3）
+pub fn round(args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::NumberValue(expect_number(args, 0, "round")?.round()))
+}
+
+// 取出整数参数（随机数的范围和种子）
+fn expect_integer(args: &[Literal], index: usize, func: &str) -> Result<f64> {
+    let n = expect_number(args, index, func)?;
+    if n.fract() != 0.0 || n.abs() > 9_007_199_254_740_992.0 {
+        return Err(RuntimeError::Runtime(
+            format!("{}() expects an integer but got {}.", func, format_number(n)),
+        ));
+    }
+    Ok(n)
+}
+
+/// random()：[0, 1) 内均匀分布的随机数
+pub fn random(interpreter: &mut Interpreter, _args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::NumberValue(interpreter.next_random()))
+}
+
+/// randomInt(lo, hi)：[lo, hi] 内的随机整数（包含两端）
+pub fn random_int(interpreter: &mut Interpreter, args: &[Literal]) -> Result<Literal> {
+    let lo = expect_integer(args, 0, "randomInt")?;
+    let hi = expect_integer(args, 1, "randomInt")?;
+    if lo > hi {
+        return Err(RuntimeError::Runtime(format!(
+            "randomInt() lower bound {} is greater than upper bound {}.",
+            format_number(lo),
+            format_number(hi)
+        )));
+    }
+    let offset = (interpreter.next_random() * (hi - lo + 1.0)).floor();
+    // 范围很大时乘法的舍入可能得到 hi + 1，截回上界
+    Ok(Literal::NumberValue((lo + offset).min(hi)))
+}
+
+/// seedRandom(n)：用整数 n 重新设置随机数种子，之后的序列可以重现
+pub fn seed_random(interpreter: &mut Interpreter, args: &[Literal]) -> Result<Literal> {
+    let seed = expect_integer(args, 0, "seedRandom")?;
+    interpreter.seed_random(seed as i64 as u64);
+    Ok(Literal::Nil)
+}
+
+/// write(x)：与 print 相同的显示形式输出，但不换行，输出后立即刷新
+pub fn write(interpreter: &mut Interpreter, args: &[Literal]) -> Result<Literal> {
+    interpreter.write_output(&args[0].to_string())?;
+    Ok(Literal::Nil)
+}
+
+/// clock()：返回自 Unix 纪元以来的秒数
+#[cfg(not(feature = "no-std"))]
+pub fn clock(_args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::NumberValue(since_unix_epoch()?.as_secs_f64()))
+}
+
+/// clockMillis()：返回自 Unix 纪元以来的整数毫秒数，便于计时
+#[cfg(not(feature = "no-std"))]
+pub fn clock_millis(_args: &[Literal]) -> Result<Literal> {
+    Ok(Literal::NumberValue(since_unix_epoch()?.as_millis() as f64))
+}
+
+#[cfg(not(feature = "no-std"))]
+fn since_unix_epoch() -> Result<std::time::Duration> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| RuntimeError::Runtime("SystemTime error.".to_string()))
+}
+
+/// readLine()：从标准输入读取一行（去掉行尾换行符），输入结束时返回 nil
+#[cfg(not(feature = "no-std"))]
+pub fn read_line(_args: &[Literal]) -> Result<Literal> {
+    let mut line = String::new();
+    let read = std::io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| RuntimeError::Runtime(format!("Failed to read input: {}.", e)))?;
+    if read == 0 {
+        return Ok(Literal::Nil);
+    }
+    let trimmed = line.strip_suffix('\n').unwrap_or(&line);
+    let trimmed = trimmed.strip_suffix('\r').unwrap_or(trimmed);
+    Ok(Literal::StringValue(trimmed.into()))
+}
+
+/// breakpoint()：开启 --interactive-debug 时暂停执行并进入调试 REPL，否则什么也不做
+#[cfg(not(feature = "no-std"))]
+pub fn breakpoint(interpreter: &mut Interpreter, _args: &[Literal]) -> Result<Literal> {
+    interpreter.debug_break()?;
+    Ok(Literal::Nil)
+}
+
+/// sleep(seconds)：阻塞当前线程；--deterministic 下只检查参数，立即返回
+#[cfg(not(feature = "no-std"))]
+pub fn sleep(interpreter: &mut Interpreter, args: &[Literal]) -> Result<Literal> {
+    let seconds = match &args[0] {
+        Literal::NumberValue(n) => *n,
+        other => {
+            return Err(RuntimeError::Runtime(
+                format!("sleep() expects a number but got {}.", other.type_name()),
+            ));
+        }
+    };
+    // 负数、NaN 和超出 Duration 范围的值都不合法
+    let duration = std::time::Duration::try_from_secs_f64(seconds).map_err(|_| {
+        RuntimeError::Runtime(format!(
+            "sleep() expects a non-negative number of seconds but got {}.",
+            format_number(seconds)
+        ))
+    })?;
+    if !interpreter.is_deterministic() {
+        std::thread::sleep(duration);
+    }
+    Ok(Literal::Nil)
+}
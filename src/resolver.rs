@@ -0,0 +1,417 @@
+use crate::expr::Expr;
+use crate::statement::Stmt;
+use crate::syntaxer::ParseError;
+use crate::token::Token;
+use std::collections::HashMap;
+
+/// 变量解析：在执行前静态计算每个局部变量引用所在的作用域深度。
+///
+/// 作用域栈与解释器运行时创建的环境一一对应，结果以表达式编号为键记录在
+/// 侧表中；查不到的名字视为全局变量。闭包因此捕获的是定义时可见的绑定，
+/// 而不是调用时按名字重新查找到的绑定。
+///
+/// 每个作用域内的变量按声明顺序分配槽位（块内的函数名提前声明，排在最前），
+/// 解释器按同样的顺序定义局部变量，运行时直接按 (深度, 槽位) 访问。
+pub struct Resolver {
+    scopes: Vec<HashMap<String, (Binding, usize)>>, // 变量名 -> (状态, 槽位)
+    locals: HashMap<usize, (usize, usize)>,      // 表达式编号 -> (作用域深度, 槽位)
+    errors: Vec<ParseError>,
+}
+
+// 作用域中名字的状态
+#[derive(Clone, Copy, PartialEq)]
+enum Binding {
+    Hoisted,  // 块内函数名提前定义，还没有解析到函数声明语句
+    Declared, // 已声明，初始化表达式还没有解析完
+    Defined,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: Vec::new(),
+            locals: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// 解析整段程序，返回局部变量的 (深度, 槽位) 表；有错误时返回全部错误
+    pub fn resolve(mut self, statements: &[Stmt]) -> Result<HashMap<usize, (usize, usize)>, Vec<ParseError>> {
+        self.resolve_stmts(statements);
+        if self.errors.is_empty() {
+            Ok(self.locals)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn resolve_stmts(&mut self, statements: &[Stmt]) {
+        // 同一作用域内的函数名提前定义，使块内函数可以相互递归调用
+        for stmt in statements {
+            if let Stmt::Function { name, .. } = stmt {
+                self.mark(&name.lexeme, Binding::Hoisted);
+            }
+        }
+        for stmt in statements {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                self.resolve_stmts(statements);
+                self.end_scope();
+            }
+            Stmt::Expression { expression } | Stmt::Print { expression } => {
+                self.resolve_expr(expression);
+            }
+            Stmt::VarDecl { name, initializer } => {
+                // 全局作用域允许重复声明（因此内置函数也可以被覆盖），局部作用域内的重复声明报错；
+                // 提前定义的块内函数名不算声明
+                if self
+                    .scopes
+                    .last()
+                    .is_some_and(|scope| scope.get(&name.lexeme).is_some_and(|(b, _)| *b != Binding::Hoisted))
+                {
+                    self.error(name, &format!("Already a variable with name '{}' in this scope.", name.lexeme));
+                }
+                // 先声明后定义，这样初始化表达式中引用自身可以被发现
+                self.declare(name);
+                if let Some(init) = initializer {
+                    self.resolve_expr(init);
+                }
+                self.define(name);
+            }
+            Stmt::MultiVarDecl { declarations } => {
+                for declaration in declarations {
+                    self.resolve_stmt(declaration);
+                }
+            }
+            Stmt::Function {
+                name, params, body, ..
+            } => {
+                // 先定义函数名，函数体内才能递归调用自身
+                self.declare(name);
+                self.define(name);
+                self.resolve_function(params, body);
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                statics,
+                fields,
+            } => {
+                self.declare(name);
+                self.define(name);
+                self.resolve_class(superclass, methods, statics, fields);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                // else if 链逐个处理，不随分支数递归
+                let mut else_branch = else_branch;
+                while let Some(branch) = else_branch {
+                    match branch.as_ref() {
+                        Stmt::If {
+                            condition,
+                            then_branch,
+                            else_branch: next,
+                        } => {
+                            self.resolve_expr(condition);
+                            self.resolve_stmt(then_branch);
+                            else_branch = next;
+                        }
+                        other => {
+                            self.resolve_stmt(other);
+                            break;
+                        }
+                    }
+                }
+            }
+            Stmt::While { condition, body } | Stmt::DoWhile { body, condition } => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(body);
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                // 对应解释器为循环创建的环境
+                self.begin_scope();
+                if let Some(init) = initializer {
+                    self.resolve_stmt(init);
+                }
+                if let Some(cond) = condition {
+                    self.resolve_expr(cond);
+                }
+                if let Some(inc) = increment {
+                    self.resolve_expr(inc);
+                }
+                self.resolve_stmt(body);
+                self.end_scope();
+            }
+            Stmt::ForEach {
+                name,
+                iterable,
+                body,
+            } => {
+                // 对应解释器每轮为循环变量创建的环境
+                self.resolve_expr(iterable);
+                self.begin_scope();
+                self.declare(name);
+                self.define(name);
+                self.resolve_stmt(body);
+                self.end_scope();
+            }
+            Stmt::Switch {
+                discriminant,
+                cases,
+                default,
+            } => {
+                self.resolve_expr(discriminant);
+                // 每个分支对应解释器中的一个代码块
+                for (value, body) in cases {
+                    self.resolve_expr(value);
+                    self.begin_scope();
+                    self.resolve_stmts(body);
+                    self.end_scope();
+                }
+                if let Some(body) = default {
+                    self.begin_scope();
+                    self.resolve_stmts(body);
+                    self.end_scope();
+                }
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::Try {
+                body,
+                catch_name,
+                handler,
+            } => {
+                self.begin_scope();
+                self.resolve_stmts(body);
+                self.end_scope();
+
+                self.begin_scope();
+                self.declare(catch_name);
+                self.define(catch_name);
+                self.resolve_stmts(handler);
+                self.end_scope();
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+            Stmt::Throw { value } => self.resolve_expr(value),
+            Stmt::With {
+                resource,
+                name,
+                body,
+                ..
+            } => {
+                self.resolve_expr(resource);
+                self.begin_scope();
+                self.declare(name);
+                self.define(name);
+                self.resolve_stmts(body);
+                self.end_scope();
+            }
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable { id, name } => {
+                if self
+                    .scopes
+                    .last()
+                    .is_some_and(|scope| matches!(scope.get(&name.lexeme), Some((Binding::Declared, _))))
+                {
+                    self.error(name, "Can't read local variable in its own initializer.");
+                }
+                self.resolve_local(*id, name);
+            }
+            Expr::Assign { id, name, value } => {
+                self.resolve_expr(value);
+                self.resolve_local(*id, name);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Unary { right, .. } => self.resolve_expr(right),
+            Expr::Grouping { expression } => self.resolve_expr(expression),
+            Expr::Literal { .. } => {}
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expr(callee);
+                for arg in arguments {
+                    self.resolve_expr(arg);
+                }
+            }
+            Expr::GetAttribute { object, .. } => self.resolve_expr(object),
+            Expr::ListLiteral { elements } => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::MapLiteral { entries, .. } => {
+                for (key, value) in entries {
+                    self.resolve_expr(key);
+                    self.resolve_expr(value);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+                self.resolve_expr(value);
+            }
+            Expr::Set { object, value, .. } => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::This { id, keyword } | Expr::Super { id, keyword, .. } => {
+                self.resolve_local(*id, keyword);
+            }
+            Expr::CompoundAssign { target, value, .. } => {
+                self.resolve_expr(value);
+                self.resolve_expr(target);
+            }
+            Expr::Lambda { params, body, .. } => self.resolve_function(params, body),
+            Expr::ClassExpr {
+                superclass,
+                methods,
+                statics,
+                fields,
+            } => self.resolve_class(superclass, methods, statics, fields),
+        }
+    }
+
+    // 函数参数与函数体共用一个作用域，对应解释器中的调用环境
+    fn resolve_function(&mut self, params: &[Token], body: &[Stmt]) {
+        self.begin_scope();
+        for param in params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_stmts(body);
+        self.end_scope();
+    }
+
+    // 类环境（绑定 super）之内是绑定 this 的环境，方法体和字段初始化都在其中解析；
+    // static 方法不绑定 this，直接在类环境中解析
+    fn resolve_class(
+        &mut self,
+        superclass: &Option<Box<Expr>>,
+        methods: &[Stmt],
+        statics: &[Stmt],
+        fields: &[Stmt],
+    ) {
+        if let Some(superclass) = superclass {
+            self.resolve_expr(superclass);
+        }
+
+        self.begin_scope();
+        if superclass.is_some() {
+            self.define_name("super");
+        }
+        self.begin_scope();
+        self.define_name("this");
+
+        for field in fields {
+            if let Stmt::VarDecl {
+                initializer: Some(init),
+                ..
+            } = field
+            {
+                self.resolve_expr(init);
+            }
+        }
+        for method in methods {
+            if let Stmt::Function { params, body, .. } = method {
+                self.resolve_function(params, body);
+            }
+        }
+        self.end_scope();
+
+        for method in statics {
+            if let Stmt::Function { params, body, .. } = method {
+                self.resolve_function(params, body);
+            }
+        }
+        self.end_scope();
+    }
+
+    fn resolve_local(&mut self, id: usize, name: &Token) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if let Some((_, slot)) = scope.get(&name.lexeme) {
+                self.locals.insert(id, (depth, *slot));
+                return;
+            }
+        }
+        // 所有局部作用域都找不到，按全局变量处理
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        self.mark(&name.lexeme, Binding::Declared);
+    }
+
+    fn define(&mut self, name: &Token) {
+        self.define_name(&name.lexeme);
+    }
+
+    fn define_name(&mut self, name: &str) {
+        self.mark(name, Binding::Defined);
+    }
+
+    // 同名变量再次声明时沿用原来的槽位，与解释器中覆盖同名局部变量一致
+    fn mark(&mut self, name: &str, binding: Binding) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let slot = scope.get(name).map_or(scope.len(), |(_, slot)| *slot);
+            scope.insert(name.to_string(), (binding, slot));
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(ParseError {
+            token: token.clone(),
+            message: message.to_string(),
+            expected: None,
+            found: None,
+        });
+    }
+}
@@ -1,11 +1,13 @@
 use std::collections::HashMap;
-use crate::token::{Token, TokenType, Literal};
+use crate::token::{format_position, Token, TokenType, Literal};
 
 pub struct Scanner {
     source: Vec<char>,
     current: usize,    // 当前扫描位置（绝对索引）
     start: usize,     // 当前词素起始位置
     line: usize,      // 当前行号
+    column: usize,    // 下一个待读字符所在列（从 1 开始）
+    start_column: usize, // 当前词素起始列
     had_error: bool,  // 错误状态标记
 }
 
@@ -30,12 +32,29 @@ impl Scanner {
         keywords.insert("true", TokenType::True);
         keywords.insert("var", TokenType::Var);
         keywords.insert("while", TokenType::While);
+        keywords.insert("try", TokenType::Try);
+        keywords.insert("catch", TokenType::Catch);
+        keywords.insert("throw", TokenType::Throw);
+        keywords.insert("with", TokenType::With);
+        keywords.insert("typeof", TokenType::TypeOf);
+        keywords.insert("break", TokenType::Break);
+        keywords.insert("continue", TokenType::Continue);
+        keywords.insert("unless", TokenType::Unless);
+        keywords.insert("until", TokenType::Until);
+        keywords.insert("in", TokenType::In);
+        keywords.insert("do", TokenType::Do);
+        keywords.insert("switch", TokenType::Switch);
+        keywords.insert("case", TokenType::Case);
+        keywords.insert("default", TokenType::Default);
+        keywords.insert("static", TokenType::Static);
 
         Self {
             source: source.chars().collect(),
             current: 0,
             start: 0,
             line: 1,
+            column: 1,
+            start_column: 1,
             had_error: false,
         }
     }
@@ -48,7 +67,8 @@ impl Scanner {
         loop {
             let token = self.scan_token();
             if token.token_type == TokenType::Error {
-                errors.push(format!("[line {}] {}", token.line, token.lexeme));
+                // 错误 Token 的词素已带有行号
+                errors.push(token.lexeme.clone());
                 self.had_error = true;
             }
             let is_eof = matches!(token.token_type, TokenType::Eof);
@@ -67,6 +87,7 @@ impl Scanner {
     pub fn scan_token(&mut self) -> Token {
         self.skip_whitespace();
         self.start = self.current;
+        self.start_column = self.column;
 
         if self.is_at_end() {
             return self.make_token(TokenType::Eof);
@@ -80,12 +101,27 @@ impl Scanner {
             ')' => self.make_token(TokenType::RightParen),
             '{' => self.make_token(TokenType::LeftBrace),
             '}' => self.make_token(TokenType::RightBrace),
+            '[' => self.make_token(TokenType::LeftBracket),
+            ']' => self.make_token(TokenType::RightBracket),
             ',' => self.make_token(TokenType::Comma),
+            // .5 在词素边界处是数字 0.5，紧跟在标识符、右括号等之后时仍是属性访问的点
+            '.' if self.peek().is_ascii_digit() && !self.follows_operand() => self.scan_number(),
+            '.' if self.peek() == '.' && self.peek_next() == '.' => {
+                self.advance();
+                self.advance();
+                self.make_token(TokenType::Ellipsis)
+            }
             '.' => self.make_token(TokenType::Dot),
-            '-' => self.make_token(TokenType::Minus),
-            '+' => self.make_token(TokenType::Plus),
+            '-' => self.make_dual_char_token('=', TokenType::MinusEqual, TokenType::Minus),
+            '+' => self.make_dual_char_token('=', TokenType::PlusEqual, TokenType::Plus),
             ';' => self.make_token(TokenType::Semicolon),
-            '*' => self.make_token(TokenType::Star),
+            ':' => self.make_token(TokenType::Colon),
+            '*' => self.make_dual_char_token('=', TokenType::StarEqual, TokenType::Star),
+            '%' => self.make_token(TokenType::Percent),
+            '&' => self.make_token(TokenType::Ampersand),
+            '|' => self.make_token(TokenType::Pipe),
+            '^' => self.make_token(TokenType::Caret),
+            '~' => self.make_token(TokenType::Tilde),
             '/' => {
                 if self.match_char('/') {
                     // 处理单行注释
@@ -93,6 +129,15 @@ impl Scanner {
                         self.advance();
                     }
                     self.scan_token() // 递归调用跳过注释
+                } else if self.match_char('*') {
+                    // 块注释，支持嵌套
+                    let start_line = self.line;
+                    if !self.skip_block_comment() {
+                        return self.error_token_at(start_line, self.start_column, "Unterminated block comment");
+                    }
+                    self.scan_token()
+                } else if self.match_char('=') {
+                    self.make_token(TokenType::SlashEqual)
                 } else {
                     self.make_token(TokenType::Slash)
                 }
@@ -101,10 +146,13 @@ impl Scanner {
             // 双字符操作符
             '!' => self.make_dual_char_token('=', TokenType::BangEqual, TokenType::Bang),
             '=' => self.make_dual_char_token('=', TokenType::EqualEqual, TokenType::Equal),
+            '<' if self.match_char('<') => self.make_token(TokenType::LessLess),
+            '>' if self.match_char('>') => self.make_token(TokenType::GreaterGreater),
             '<' => self.make_dual_char_token('=', TokenType::LessEqual, TokenType::Less),
             '>' => self.make_dual_char_token('=', TokenType::GreaterEqual, TokenType::Greater),
             
             // 字符串字面量
+            '"' if self.peek() == '"' && self.peek_next() == '"' => self.scan_raw_string(),
             '"' => self.scan_string(),
             
             // 数字字面量
@@ -120,6 +168,7 @@ impl Scanner {
 
     /// 扫描字符串字面量
     fn scan_string(&mut self) -> Token {
+        let start_line = self.line; // 字符串可以跨行，未闭合时报告起始位置
         let mut value = String::new();
         let mut error = None;
 
@@ -127,16 +176,30 @@ impl Scanner {
             if self.peek() == '\n' {
                 self.line += 1;
             }
+            let (line, column) = (self.line, self.column);
             let c = self.advance();
             if c == '\\' {
                 // 处理转义字符
-                match self.advance() {
-                    'n' => value.push('\n'),
-                    't' => value.push('\t'),
-                    'r' => value.push('\r'),
-                    '"' => value.push('"'),
-                    '\\' => value.push('\\'),
-                    esc => error = Some(format!("Invalid escape sequence \\{}", esc)),
+                if self.peek() == '\n' {
+                    self.line += 1;
+                }
+                let escaped = match self.advance() {
+                    'n' => Ok('\n'),
+                    't' => Ok('\t'),
+                    'r' => Ok('\r'),
+                    '0' => Ok('\0'),
+                    'a' => Ok('\x07'),
+                    '"' => Ok('"'),
+                    '\\' => Ok('\\'),
+                    'u' => self.scan_unicode_escape(),
+                    esc => Err(format!("Invalid escape sequence \\{}", esc)),
+                };
+                match escaped {
+                    Ok(ch) => value.push(ch),
+                    // 只报告第一个非法转义，位置指向其反斜杠
+                    Err(message) => {
+                        error.get_or_insert((line, column, message));
+                    }
                 }
             } else {
                 value.push(c);
@@ -144,43 +207,144 @@ impl Scanner {
         }
 
         if self.is_at_end() {
-            return self.error_token("Unterminated string");
+            return self.error_token_at(start_line, self.start_column, "Unterminated string");
         }
 
         self.advance(); // 消耗闭合引号
 
-        if let Some(err) = error {
-            self.error_token(&err)
+        if let Some((line, column, message)) = error {
+            self.error_token_at(line, column, &message)
         } else {
-            self.make_token_with_literal(TokenType::String, Literal::StringValue(value))
+            self.make_token_with_literal(TokenType::String, Literal::StringValue(value.into()))
+        }
+    }
+
+    /// 扫描三引号原始字符串：内容原样保留（包括换行和反斜杠），直到下一个 """
+    fn scan_raw_string(&mut self) -> Token {
+        let start_line = self.line;
+        self.advance();
+        self.advance(); // 消耗开头剩余的两个引号
+
+        let mut value = String::new();
+        while !self.is_at_end() {
+            if self.peek() == '"' && self.peek_next() == '"' && self.source.get(self.current + 2) == Some(&'"') {
+                for _ in 0..3 {
+                    self.advance(); // 消耗闭合的三引号
+                }
+                return self.make_token_with_literal(TokenType::String, Literal::StringValue(value.into()));
+            }
+            if self.peek() == '\n' {
+                self.line += 1;
+            }
+            value.push(self.advance());
         }
+        self.error_token_at(start_line, self.start_column, "Unterminated raw string")
+    }
+
+    /// 扫描 \u{1F600} 形式的转义（\u 已消耗），花括号内为 1 到 6 位十六进制码点
+    fn scan_unicode_escape(&mut self) -> Result<char, String> {
+        if !self.match_char('{') {
+            return Err("Invalid unicode escape: expected '{' after \\u".to_string());
+        }
+        let mut digits = String::new();
+        while self.peek().is_ascii_hexdigit() {
+            digits.push(self.advance());
+        }
+        if !self.match_char('}') {
+            return Err("Invalid unicode escape: expected hex digits and '}'".to_string());
+        }
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(format!("Invalid unicode escape \\u{{{}}}: expected 1 to 6 hex digits", digits));
+        }
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("Invalid unicode escape \\u{{{}}}: not a Unicode scalar value", digits))
     }
 
     /// 扫描数字字面量
+    /// 数字之间可以用 _ 分隔（如 1_000_000），分隔符不能连续、不能在末尾，也不能紧挨小数点
     fn scan_number(&mut self) -> Token {
-        let mut is_float = false;
-        while self.peek().is_ascii_digit() {
-            self.advance();
+        if self.previous_char() == '0' {
+            let radix = match self.peek() {
+                'x' => Some(16),
+                'b' => Some(2),
+                'o' => Some(8),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                return self.scan_radix_number(radix);
+            }
         }
 
-        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
-            is_float = true;
-            self.advance(); // 消耗小数点
-            while self.peek().is_ascii_digit() {
-                self.advance();
+        let mut error = self.scan_digits();
+
+        let leading_dot = self.source[self.start] == '.';
+        if !leading_dot && self.peek() == '.' && (self.peek_next().is_ascii_digit() || self.peek_next() == '_') {
+            if self.previous_char() == '_' || self.peek_next() == '_' {
+                error = error.or(Some("digit separator next to decimal point"));
             }
+            self.advance(); // 消耗小数点
+            error = error.or(self.scan_digits());
+        }
+        if self.previous_char() == '_' {
+            error = error.or(Some("trailing digit separator"));
         }
 
         let num_str: String = self.source[self.start..self.current].iter().collect();
-        match num_str.parse() {
-            Ok(num) => self.make_token_with_literal(
-                TokenType::Number,
-                if is_float { Literal::NumberValue(num) } else { Literal::NumberValue(num as f64) }
-            ),
+        if let Some(reason) = error {
+            return self.error_token(&format!("Invalid number {}: {}", num_str, reason));
+        }
+        match num_str.replace('_', "").parse() {
+            Ok(num) => self.make_token_with_literal(TokenType::Number, Literal::NumberValue(num)),
             Err(_) => self.error_token(&format!("Invalid number {}", num_str)),
         }
     }
 
+    /// 扫描 0x1F、0b1010、0o17 形式的整数字面量，同样允许 _ 分隔数字
+    fn scan_radix_number(&mut self, radix: u32) -> Token {
+        self.advance(); // 消耗进制前缀字母
+        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
+            self.advance();
+        }
+
+        let num_str: String = self.source[self.start..self.current].iter().collect();
+        let digits = &num_str[2..];
+        let error = if digits.is_empty() {
+            Some("missing digits".to_string())
+        } else if let Some(c) = digits.chars().find(|c| *c != '_' && !c.is_digit(radix)) {
+            Some(format!("invalid digit '{}' for base {}", c, radix))
+        } else if digits.starts_with('_') {
+            Some("digit separator after base prefix".to_string())
+        } else if digits.contains("__") {
+            Some("doubled digit separator".to_string())
+        } else if digits.ends_with('_') {
+            Some("trailing digit separator".to_string())
+        } else {
+            None
+        };
+        if let Some(reason) = error {
+            return self.error_token(&format!("Invalid number {}: {}", num_str, reason));
+        }
+
+        match u64::from_str_radix(&digits.replace('_', ""), radix) {
+            Ok(num) => self.make_token_with_literal(TokenType::Number, Literal::NumberValue(num as f64)),
+            Err(_) => self.error_token(&format!("Invalid number {}: too large", num_str)),
+        }
+    }
+
+    // 消耗一段数字和分隔符，出现连续分隔符时返回错误原因
+    fn scan_digits(&mut self) -> Option<&'static str> {
+        let mut error = None;
+        while self.peek().is_ascii_digit() || self.peek() == '_' {
+            if self.peek() == '_' && self.previous_char() == '_' {
+                error = Some("doubled digit separator");
+            }
+            self.advance();
+        }
+        error
+    }
+
     /// 统一标识符扫描方法
     fn scan_identifier(&mut self) -> Token {
         while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
@@ -209,6 +373,21 @@ impl Scanner {
             "true" => TokenType::True,
             "var" => TokenType::Var,
             "while" => TokenType::While,
+            "try" => TokenType::Try,
+            "catch" => TokenType::Catch,
+            "throw" => TokenType::Throw,
+            "with" => TokenType::With,
+            "typeof" => TokenType::TypeOf,
+            "break" => TokenType::Break,
+            "continue" => TokenType::Continue,
+            "unless" => TokenType::Unless,
+            "until" => TokenType::Until,
+            "in" => TokenType::In,
+            "do" => TokenType::Do,
+            "switch" => TokenType::Switch,
+            "case" => TokenType::Case,
+            "default" => TokenType::Default,
+            "static" => TokenType::Static,
             _ => TokenType::Identifier, 
         };
         
@@ -217,7 +396,7 @@ impl Scanner {
     /// 创建带字面量的 token
     fn make_token_with_literal(&self, token_type: TokenType, literal: Literal) -> Token {
         let lexeme = self.source[self.start..self.current].iter().collect();
-        Token::new(token_type, self.line, lexeme, Some(literal))
+        Token::new(token_type, self.line, lexeme, Some(literal)).with_column(self.start_column)
     }
 
     /// 处理双字符操作符
@@ -237,7 +416,13 @@ impl Scanner {
     /// 移动指针并返回当前字符
     fn advance(&mut self) -> char {
         self.current += 1;
-        self.source.get(self.current - 1).copied().unwrap_or('\0')
+        let c = self.source.get(self.current - 1).copied().unwrap_or('\0');
+        if c == '\n' {
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        c
     }
 
     /// 跳过空白字符
@@ -262,11 +447,47 @@ impl Scanner {
         }
     }
 
+    /// 跳过块注释的剩余部分（开头的 `/*` 已消耗），返回注释是否正常闭合
+    fn skip_block_comment(&mut self) -> bool {
+        let mut depth = 1;
+        while !self.is_at_end() {
+            match self.advance() {
+                '\n' => self.line += 1,
+                '/' if self.peek() == '*' => {
+                    self.advance();
+                    depth += 1;
+                }
+                '*' if self.peek() == '/' => {
+                    self.advance();
+                    depth -= 1;
+                    if depth == 0 {
+                        return true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+
     /// 查看下一个字符
     fn peek(&self) -> char {
         self.source.get(self.current).copied().unwrap_or('\0')
     }
 
+    /// 当前词素是否紧跟在标识符、数字、字符串或右括号之后（中间没有空白）
+    fn follows_operand(&self) -> bool {
+        self.start > 0 && {
+            let c = self.source[self.start - 1];
+            c.is_ascii_alphanumeric() || matches!(c, '_' | ')' | ']' | '}' | '"')
+        }
+    }
+
+    /// 上一个已消耗的字符
+    fn previous_char(&self) -> char {
+        self.source[self.current - 1]
+    }
+
     /// 查看下下个字符
     fn peek_next(&self) -> char {
         self.source.get(self.current + 1).copied().unwrap_or('\0')
@@ -277,7 +498,7 @@ impl Scanner {
         if self.is_at_end() || self.source[self.current] != expected {
             return false;
         }
-        self.current += 1;
+        self.advance();
         true
     }
 
@@ -292,17 +513,24 @@ impl Scanner {
             lexeme,
             None
         )
+        .with_column(self.start_column)
     }
 
     /// 带错误信息的 token
     fn error_token(&mut self, message: &str) -> Token {
+        self.error_token_at(self.line, self.start_column, message)
+    }
+
+    /// 在指定位置报告错误（用于跨行的字符串和注释）
+    fn error_token_at(&mut self, line: usize, column: usize, message: &str) -> Token {
         self.had_error = true;
         Token::new(
             TokenType::Error,
-            self.line,
-            format!("[line {}] {}", self.line, message),
+            line,
+            format!("[{}] {}", format_position(line, column), message),
             None
         )
+        .with_column(column)
     }
     /// 检查是否到达输入结尾
     fn is_at_end(&self) -> bool {
@@ -14,15 +14,23 @@ pub enum Stmt {
         name: Token,
         initializer: Option<Expr>,
     },
+    // var a = 1, b; 按顺序在当前作用域中声明，不引入新的作用域
+    MultiVarDecl {
+        declarations: Vec<Stmt>, // 均为 VarDecl
+    },
     Function {
         name: Token,
         params: Vec<Token>,
+        has_rest: bool, // 最后一个参数为 ...rest，收集多余的实参
         body: Vec<Stmt>,
+        is_getter: bool, // 没有参数列表的方法，如 area { ... }
     },
     Class {
         name: Token,
         superclass: Option<Box<Expr>>, // 修改为Box包装
         methods: Vec<Stmt>,
+        statics: Vec<Stmt>, // static 方法，通过类直接调用
+        fields: Vec<Stmt>, // 字段声明，均为 VarDecl
     },
     If {
         condition: Expr,
@@ -33,17 +41,57 @@ pub enum Stmt {
         condition: Expr,
         body: Box<Stmt>,
     },
+    // 先执行一次循环体，再在条件为真时继续循环
+    DoWhile {
+        body: Box<Stmt>,
+        condition: Expr,
+    },
+    // 解析器直接生成 For 而不是脱糖为 Block + While：
+    // 解释器在同一处执行初始化、条件和增量，循环控制语句也能正确执行增量
     For {
         initializer: Option<Box<Stmt>>,
         condition: Option<Expr>,
         increment: Option<Expr>,
         body: Box<Stmt>,
     },
+    // for (name in iterable) body：遍历列表元素或字符串的字符，每轮在新作用域中绑定 name
+    ForEach {
+        name: Token,
+        iterable: Expr,
+        body: Box<Stmt>,
+    },
+    // 依次比较各 case 的值（==），只执行第一个匹配的分支，不会贯穿到下一个分支；
+    // 都不匹配时执行 default
+    Switch {
+        discriminant: Expr,
+        cases: Vec<(Expr, Vec<Stmt>)>,
+        default: Option<Vec<Stmt>>,
+    },
     Return {
         keyword: Token,
         value: Option<Expr>,
     },
+    Break {
+        keyword: Token,
+    },
+    Continue {
+        keyword: Token,
+    },
     Print {
         expression: Expr,
     },
+    Try {
+        body: Vec<Stmt>,
+        catch_name: Token, // catch 绑定的错误变量名
+        handler: Vec<Stmt>,
+    },
+    Throw {
+        value: Expr,
+    },
+    With {
+        keyword: Token,  // with关键字token，用于调用close时报告位置
+        resource: Expr,
+        name: Token,
+        body: Vec<Stmt>,
+    },
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use crate::expr::Expr;
+use crate::expr::{next_expr_id, Expr};
 use crate::statement::Stmt;
 use crate::token::{Literal, Token, TokenType};
 use std::error::Error;
@@ -9,12 +9,14 @@ use std::fmt;
 pub struct ParseError {
     pub token: Token,      // 保存触发错误的Token
     pub message: String,   // 原始错误消息
+    pub expected: Option<TokenType>, // consume 失败时期望的Token类型
+    pub found: Option<TokenType>,    // consume 失败时实际遇到的Token类型
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.message.starts_with("Error: ") {
-            // 特殊错误直接显示消息
+        if self.message.starts_with("Error: ") || self.token.token_type == TokenType::Error {
+            // 特殊错误和词法错误直接显示消息
             write!(f, "{}", self.message)
         } else {
             // 其他错误显示位置和消息
@@ -35,14 +37,45 @@ impl fmt::Display for ParseError {
 
 impl Error for ParseError {} // 实现 Error trait
 
+/// 把多条语法错误渲染为一整块文本：按行号排序，末尾附上 "N errors" 汇总
+pub fn render_parse_errors(errors: &[ParseError]) -> String {
+    let mut sorted: Vec<&ParseError> = errors.iter().collect();
+    sorted.sort_by_key(|e| (e.token.line, e.token.column));
+
+    let mut out = String::new();
+    for err in sorted {
+        if err.token.token_type == TokenType::Error {
+            // 词法错误的消息已自带行号
+            out.push_str(&format!("{}\n", err));
+        } else {
+            out.push_str(&format!("[{}] {}\n", err.token.position(), err));
+        }
+    }
+    let plural = if errors.len() == 1 { "" } else { "s" };
+    out.push_str(&format!("{} error{}", errors.len(), plural));
+    out
+}
+
 // ------------------- 语法分析器主体 -------------------
+// 类体解析结果：（超类, 方法, static 方法, 字段声明）
+type ClassBody = (Option<Box<Expr>>, Vec<Stmt>, Vec<Stmt>, Vec<Stmt>);
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
     allow_this: bool, // 新增：是否允许使用this
     in_function: bool, // 新增标志，表示当前是否在函数内
+    loop_depth: usize, // 当前所在循环的嵌套层数，用于检查 break/continue
+    in_static: bool, // 是否在 static 方法内，其中不能使用 this/super
+    trailing_expression: bool, // 是否允许源码末尾的表达式省略分号（REPL 与 -e）
+    depth: usize, // 当前表达式与语句的嵌套层数
+    max_depth: usize, // 超过该层数时报错，避免递归下降耗尽栈空间
 }
 
+/// 表达式与语句默认允许的最大嵌套层数，调试构建下在 8MB 栈的主线程中解析也不会溢出；
+/// 并列的语句和 else if 分支不计入层数
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 500;
+
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
         Self { 
@@ -50,23 +83,54 @@ impl Parser {
             current: 0, 
             allow_this: false, // 初始状态不允许
             in_function: false,
+            loop_depth: 0,
+            in_static: false,
+            trailing_expression: false,
+            depth: 0,
+            max_depth: DEFAULT_MAX_NESTING_DEPTH,
         }
     }
 
+    /// 修改表达式与语句允许的最大嵌套层数；调大时需保证解析线程的栈足够，
+    /// 调试构建下每层约需 15KB
+    pub fn set_max_depth(&mut self, limit: usize) {
+        self.max_depth = limit;
+    }
+
     // 主解析方法
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, ParseError> {
+    // 出错时记录错误并同步到下一条语句继续解析，最后一次性返回全部错误
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
         let mut statements = Vec::new();
+        let mut errors = Vec::new();
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(e) => errors.push(e),
+            }
         }
-        Ok(statements)
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// REPL 与 -e 使用的解析入口：与 parse 相同，但源码末尾的表达式可以省略分号，
+    /// 此时它被解析为 print 语句，执行时输出其值
+    pub fn parse_repl(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        self.trailing_expression = true;
+        let result = self.parse();
+        self.trailing_expression = false;
+        result
     }
 
     // --------------- 声明解析 ---------------
     fn declaration(&mut self) -> Result<Stmt, ParseError> {
         let result = if self.match_token(TokenType::Class) {
             self.class_declaration()
-        } else if self.match_token(TokenType::Fun) {
+        } else if self.check(TokenType::Fun) && self.check_next(TokenType::Identifier) {
+            // fun 后面不是函数名时是匿名函数表达式，按表达式语句解析
+            self.advance();
             self.function("function")
         } else if self.match_token(TokenType::Var) {
             self.var_declaration()
@@ -74,21 +138,31 @@ impl Parser {
             self.statement()
         };
 
-        result.or_else(|err| {
-            self.synchronize();
-            Err(err)
-        })
+        result.inspect_err(|_| self.synchronize())
     }
 
     // --------------- 类声明 ---------------
     fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
         let name = self.consume_identifier("Expect class name")?;
+        let (superclass, methods, statics, fields) = self.class_body()?;
+
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+            statics,
+            fields,
+        })
+    }
 
+    // 类声明与类表达式共用：可选的超类、方法列表和字段声明
+    fn class_body(&mut self) -> Result<ClassBody, ParseError> {
         // 修复超类解析逻辑
         let mut super_expr = None;
         if self.match_token(TokenType::Less) {
             self.consume_identifier("Error: Superclass must be a class.")?;
             super_expr = Some(Expr::Variable {
+                id: next_expr_id(),
                 name: self.previous().clone(),
             });
         }
@@ -96,37 +170,77 @@ impl Parser {
         self.consume(TokenType::LeftBrace, "Expect '{' before class body")?;
 
         let mut methods = Vec::new();
+        let mut statics = Vec::new();
+        let mut fields = Vec::new();
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
-            methods.push(self.function("method")?);
+            if self.match_token(TokenType::Var) {
+                // 字段初始化表达式中可以使用 this
+                let prev_allow_this = self.allow_this;
+                self.allow_this = true;
+                let field = self.var_declaration();
+                self.allow_this = prev_allow_this;
+                match field? {
+                    Stmt::MultiVarDecl { declarations } => fields.extend(declarations),
+                    field => fields.push(field),
+                }
+            } else if self.match_token(TokenType::Static) {
+                statics.push(self.function("static method")?);
+            } else {
+                methods.push(self.function("method")?);
+            }
         }
 
         self.consume(TokenType::RightBrace, "Expect '}' after class body")?;
 
-        Ok(Stmt::Class {
-            name,
-            superclass: super_expr.map(|e| Box::new(e)),
-            methods,
-        })
+        Ok((super_expr.map(Box::new), methods, statics, fields))
     }
 
     // --------------- 函数声明 ---------------
     fn function(&mut self, kind: &str) -> Result<Stmt, ParseError> {
         let name = self.consume_identifier(&format!("Expect {} name", kind))?;
+        // 方法名后直接是函数体的为 getter：没有参数，通过属性访问时立即调用
+        if kind == "method" && self.match_token(TokenType::LeftBrace) {
+            let body = self.function_body(kind)?;
+            return Ok(Stmt::Function {
+                name,
+                params: Vec::new(),
+                has_rest: false,
+                body,
+                is_getter: true,
+            });
+        }
         self.consume(
             TokenType::LeftParen,
             &format!("Expect '(' after {} name", kind),
         )?;
+        let (params, has_rest, body) = self.function_rest(kind)?;
+        Ok(Stmt::Function {
+            name,
+            params,
+            has_rest,
+            body,
+            is_getter: false,
+        })
+    }
 
+    // 参数列表（左括号之后）和函数体，具名函数、方法与匿名函数共用；
+    // 返回的布尔值表示最后一个参数是否为 ...rest
+    fn function_rest(&mut self, kind: &str) -> Result<(Vec<Token>, bool, Vec<Stmt>), ParseError> {
         let mut params = Vec::new();
+        let mut has_rest = false;
         if !self.check(TokenType::RightParen) {
             loop {
                 if params.len() >= 255 {
-                    return Err(self.error(&self.peek(), "Can't have more than 255 parameters"));
+                    return Err(self.error(self.peek(), "Can't have more than 255 parameters"));
                 }
+                has_rest = self.match_token(TokenType::Ellipsis);
                 params.push(self.consume_identifier("Expect parameter name")?);
                 if !self.match_token(TokenType::Comma) {
                     break;
                 }
+                if has_rest {
+                    return Err(self.error(self.previous(), "Rest parameter must be last"));
+                }
             }
         }
 
@@ -135,50 +249,100 @@ impl Parser {
             TokenType::LeftBrace,
             &format!("Expect '{{' before {} body", kind),
         )?;
+        Ok((params, has_rest, self.function_body(kind)?))
+    }
+
+    // 函数体（左花括号之后），解析期间切换 this/static/循环相关的状态
+    fn function_body(&mut self, kind: &str) -> Result<Vec<Stmt>, ParseError> {
         // 进入方法时允许this
         let prev_allow_this = self.allow_this;
         let prev_in_function = self.in_function;
-        self.allow_this = kind == "method";
+        let prev_loop_depth = self.loop_depth;
+        let prev_in_static = self.in_static;
+        // 方法内嵌套的函数仍可访问外层方法的 this；static 方法没有 this
+        self.allow_this = kind == "method" || (prev_allow_this && kind != "static method");
+        self.in_static = match kind {
+            "static method" => true,
+            "method" => false,
+            _ => prev_in_static,
+        };
         self.in_function = true; // 标记当前在函数内
+        self.loop_depth = 0; // 函数体内不能 break/continue 外层循环
 
-        let body = self.block_statement()?;
+        // 函数声明不经过 statement，函数体在这里计入嵌套层数
+        let body = self.nested(Self::block_statement, "Statement too deeply nested");
 
         // 恢复之前的状态
         self.allow_this = prev_allow_this;
         self.in_function = prev_in_function;
-
-        Ok(Stmt::Function { name, params, body })
+        self.loop_depth = prev_loop_depth;
+        self.in_static = prev_in_static;
+        body
     }
 
     // --------------- 变量声明 ---------------
+    // var a = 1, b, c = a; 逗号分隔的多个变量生成 MultiVarDecl，只有一个时仍是 VarDecl
     fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
-        let name = self.consume_identifier("Expect variable name")?;
+        let mut declarations = Vec::new();
+        loop {
+            let name = self.consume_identifier("Expect variable name")?;
 
-        let initializer = if self.match_token(TokenType::Equal) {
-            Some(self.expression()?)
-        } else {
-            None
-        };
+            let initializer = if self.match_token(TokenType::Equal) {
+                Some(self.expression()?)
+            } else {
+                None
+            };
+            declarations.push(Stmt::VarDecl { name, initializer });
+
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
 
         self.consume(
             TokenType::Semicolon,
             "Expect ';' after variable declaration",
         )?;
-        Ok(Stmt::VarDecl { name, initializer })
+        if declarations.len() == 1 {
+            Ok(declarations.pop().unwrap())
+        } else {
+            Ok(Stmt::MultiVarDecl { declarations })
+        }
     }
 
     // --------------- 语句解析 ---------------
+    // 代码块、if/while 等语句的嵌套也经过这里，和表达式共用嵌套计数
     fn statement(&mut self) -> Result<Stmt, ParseError> {
+        self.nested(Self::statement_inner, "Statement too deeply nested")
+    }
+
+    fn statement_inner(&mut self) -> Result<Stmt, ParseError> {
         if self.match_token(TokenType::For) {
             self.for_statement()
         } else if self.match_token(TokenType::If) {
             self.if_statement()
+        } else if self.match_token(TokenType::Unless) {
+            self.unless_statement()
+        } else if self.match_token(TokenType::Until) {
+            self.until_statement()
+        } else if self.match_token(TokenType::Do) {
+            self.do_while_statement()
+        } else if self.match_token(TokenType::Switch) {
+            self.switch_statement()
         } else if self.match_token(TokenType::Print) {
             self.print_statement()
         } else if self.match_token(TokenType::Return) {
             self.return_statement()
+        } else if self.match_tokens(&[TokenType::Break, TokenType::Continue]) {
+            self.loop_control_statement()
         } else if self.match_token(TokenType::While) {
             self.while_statement()
+        } else if self.match_token(TokenType::Try) {
+            self.try_statement()
+        } else if self.match_token(TokenType::Throw) {
+            self.throw_statement()
+        } else if self.match_token(TokenType::With) {
+            self.with_statement()
         } else if self.match_token(TokenType::LeftBrace) {
             Ok(Stmt::Block {
                 statements: self.block_statement()?,
@@ -191,7 +355,15 @@ impl Parser {
     // --------------- for 语句 ---------------
     fn for_statement(&mut self) -> Result<Stmt, ParseError> {
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'")?;
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::In) {
+            self.for_each_rest()
+        } else {
+            self.for_clauses_rest()
+        }
+    }
 
+    // for (初始化; 条件; 增量) 中 '(' 之后的部分
+    fn for_clauses_rest(&mut self) -> Result<Stmt, ParseError> {
         let initializer = if self.match_token(TokenType::Semicolon) {
             None
         } else if self.match_token(TokenType::Var) {
@@ -214,27 +386,30 @@ impl Parser {
         };
         self.consume(TokenType::RightParen, "Expect ')' after for clauses")?;
 
-        let mut body = self.statement()?;
-
-        if let Some(inc) = increment {
-            body = Stmt::Block {
-                statements: vec![body, Stmt::Expression { expression: inc }],
-            };
-        }
+        let body = self.loop_body()?;
 
-        let while_loop = Stmt::While {
-            condition: condition.unwrap_or(Expr::Literal {
-                value: Literal::Boolean(true),
-            }),
+        // 保留 for 结构交给解释器执行，而不是脱糖为 while，
+        // 以便循环控制语句仍能执行增量表达式
+        Ok(Stmt::For {
+            initializer: initializer.map(Box::new),
+            condition,
+            increment,
             body: Box::new(body),
-        };
+        })
+    }
 
-        Ok(if let Some(init) = initializer {
-            Stmt::Block {
-                statements: vec![init, while_loop],
-            }
-        } else {
-            while_loop
+    // for (x in collection) 中 '(' 之后的部分
+    fn for_each_rest(&mut self) -> Result<Stmt, ParseError> {
+        self.advance();
+        let name = self.previous().clone();
+        self.advance(); // 消耗 in
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after for-in collection")?;
+        let body = self.loop_body()?;
+        Ok(Stmt::ForEach {
+            name,
+            iterable,
+            body: Box::new(body),
         })
     }
 
@@ -250,17 +425,82 @@ impl Parser {
 
     // --------------- if 语句 ---------------
     fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        let condition = self.if_condition()?;
+        self.if_rest(condition)
+    }
+
+    // unless 语句：条件取反后按 if 处理，同样可以带 else
+    fn unless_statement(&mut self) -> Result<Stmt, ParseError> {
+        let condition = self.unless_condition()?;
+        self.if_rest(condition)
+    }
+
+    // if 关键字之后的括号条件
+    fn if_condition(&mut self) -> Result<Expr, ParseError> {
         self.consume(TokenType::LeftParen, "Expect '(' after 'if'")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after if condition")?;
+        Ok(condition)
+    }
 
+    // unless 关键字之后的括号条件，返回取反后的条件
+    fn unless_condition(&mut self) -> Result<Expr, ParseError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'unless'")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after unless condition")?;
+        Ok(Self::negate(&keyword, condition))
+    }
+
+    // until 语句：条件取反后按 while 处理
+    fn until_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'until'")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition")?;
+        let body = Box::new(self.loop_body()?);
+        Ok(Stmt::While {
+            condition: Self::negate(&keyword, condition),
+            body,
+        })
+    }
+
+    // 构造 !condition，运算符位置取关键字的位置
+    fn negate(keyword: &Token, condition: Expr) -> Expr {
+        Expr::Unary {
+            operator: Token::new(TokenType::Bang, keyword.line, "!".to_string(), None)
+                .with_column(keyword.column),
+            right: Box::new(condition),
+        }
+    }
+
+    // if 的分支部分，if 与 unless 共用。
+    // else if / else unless 在这里循环解析，不经过 statement，
+    // 所以再长的 else if 链也不计入嵌套层数
+    fn if_rest(&mut self, condition: Expr) -> Result<Stmt, ParseError> {
         let then_branch = Box::new(self.statement()?);
-        let else_branch = if self.match_token(TokenType::Else) {
-            Some(Box::new(self.statement()?))
-        } else {
-            None
-        };
+        let mut else_ifs = Vec::new();
+        let mut else_branch = None;
+        while self.match_token(TokenType::Else) {
+            let condition = if self.match_token(TokenType::If) {
+                self.if_condition()?
+            } else if self.match_token(TokenType::Unless) {
+                self.unless_condition()?
+            } else {
+                else_branch = Some(Box::new(self.statement()?));
+                break;
+            };
+            else_ifs.push((condition, self.statement()?));
+        }
 
+        // 从最后一个 else if 向前折叠成嵌套的 If
+        for (condition, branch) in else_ifs.into_iter().rev() {
+            else_branch = Some(Box::new(Stmt::If {
+                condition,
+                then_branch: Box::new(branch),
+                else_branch,
+            }));
+        }
         Ok(Stmt::If {
             condition,
             then_branch,
@@ -271,6 +511,9 @@ impl Parser {
     // --------------- 表达式语句 ---------------
     fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
         let expr = self.expression()?;
+        if self.trailing_expression && self.is_at_end() {
+            return Ok(Stmt::Print { expression: expr });
+        }
         self.consume(TokenType::Semicolon, "Expect ';' after expression")?;
         Ok(Stmt::Expression { expression: expr })
     }
@@ -307,147 +550,259 @@ impl Parser {
         self.consume(TokenType::LeftParen, "Expect '(' after 'while'")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after condition")?;
-        let body = Box::new(self.statement()?);
+        let body = Box::new(self.loop_body()?);
         Ok(Stmt::While { condition, body })
     }
 
-    // --------------- 表达式解析 ---------------
-    fn expression(&mut self) -> Result<Expr, ParseError> {
-        self.assignment()
+    // --------------- do-while 语句 ---------------
+    fn do_while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let body = Box::new(self.loop_body()?);
+        self.consume(TokenType::While, "Expect 'while' after do body")?;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition")?;
+        self.consume(TokenType::Semicolon, "Expect ';' after do-while condition")?;
+        Ok(Stmt::DoWhile { body, condition })
     }
 
-    fn assignment(&mut self) -> Result<Expr, ParseError> {
-        let expr = self.logic_or()?;
-
-        // 处理对象属性赋值 obj.x = 5
-        if self.match_token(TokenType::Equal) {
-            let equals = self.previous().clone();
-            let value = self.assignment()?;
-
-            if let Expr::Variable { name } = expr {
-                return Ok(Expr::Assign {
-                    name,
-                    value: Box::new(value),
-                });
-            } else if let Expr::GetAttribute { object, name } = expr {
-                return Ok(Expr::Set {
-                    object,
-                    name,
-                    value: Box::new(value),
-                });
-            }
+    // 循环体内允许 break/continue
+    fn loop_body(&mut self) -> Result<Stmt, ParseError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
 
-            return Err(self.error(&equals, "Invalid assignment target"));
+    // --------------- break/continue 语句 ---------------
+    fn loop_control_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        if self.loop_depth == 0 {
+            return Err(self.error(
+                &keyword,
+                &format!("Error: Can't use '{}' outside of a loop.", keyword.lexeme),
+            ));
+        }
+        self.consume(
+            TokenType::Semicolon,
+            &format!("Expect ';' after '{}'", keyword.lexeme),
+        )?;
+        if keyword.token_type == TokenType::Break {
+            Ok(Stmt::Break { keyword })
+        } else {
+            Ok(Stmt::Continue { keyword })
         }
+    }
 
-        Ok(expr)
+    // --------------- try/catch 语句 ---------------
+    fn try_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'")?;
+        let body = self.block_statement()?;
+
+        self.consume(TokenType::Catch, "Expect 'catch' after try block")?;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'catch'")?;
+        let catch_name = self.consume_identifier("Expect error variable name")?;
+        self.consume(TokenType::RightParen, "Expect ')' after error variable")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before catch body")?;
+        let handler = self.block_statement()?;
+
+        Ok(Stmt::Try {
+            body,
+            catch_name,
+            handler,
+        })
     }
 
-    fn logic_or(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.logic_and()?;
+    // --------------- switch 语句 ---------------
+    fn switch_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'switch'")?;
+        let discriminant = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after switch value")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before switch body")?;
 
-        while self.match_token(TokenType::Or) {
-            let operator = self.previous().clone();
-            let right = self.logic_and()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+        let mut cases = Vec::new();
+        let mut default = None;
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if self.match_token(TokenType::Case) {
+                let value = self.expression()?;
+                self.consume(TokenType::Colon, "Expect ':' after case value")?;
+                cases.push((value, self.switch_arm()?));
+            } else if self.match_token(TokenType::Default) {
+                if default.is_some() {
+                    return Err(self.error(self.previous(), "Multiple 'default' labels in switch"));
+                }
+                self.consume(TokenType::Colon, "Expect ':' after 'default'")?;
+                default = Some(self.switch_arm()?);
+            } else {
+                return Err(self.error(self.peek(), "Expect 'case' or 'default' in switch body"));
+            }
         }
+        self.consume(TokenType::RightBrace, "Expect '}' after switch body")?;
 
-        Ok(expr)
+        Ok(Stmt::Switch {
+            discriminant,
+            cases,
+            default,
+        })
     }
 
-    fn logic_and(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.equality()?;
-
-        while self.match_token(TokenType::And) {
-            let operator = self.previous().clone();
-            let right = self.equality()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+    // 一个分支的语句，直到下一个 case/default 或 switch 结束
+    fn switch_arm(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut statements = Vec::new();
+        while !self.check(TokenType::Case)
+            && !self.check(TokenType::Default)
+            && !self.check(TokenType::RightBrace)
+            && !self.is_at_end()
+        {
+            statements.push(self.declaration()?);
         }
-
-        Ok(expr)
+        Ok(statements)
     }
 
-    fn equality(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.comparison()?;
+    // --------------- throw 语句 ---------------
+    fn throw_statement(&mut self) -> Result<Stmt, ParseError> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after thrown value")?;
+        Ok(Stmt::Throw { value })
+    }
 
-        while self.match_tokens(&[TokenType::BangEqual, TokenType::EqualEqual]) {
-            let operator = self.previous().clone();
-            let right = self.comparison()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+    // --------------- with 语句 ---------------
+    // with (expr as name) { body }，其中 as 是上下文关键字，不占用标识符
+    fn with_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'with'")?;
+        let resource = self.expression()?;
+        if !(self.check(TokenType::Identifier) && self.peek().lexeme == "as") {
+            return Err(self.error(self.peek(), "Expect 'as' after with resource"));
         }
+        self.advance();
+        let name = self.consume_identifier("Expect resource name after 'as'")?;
+        self.consume(TokenType::RightParen, "Expect ')' after resource name")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before with body")?;
+        let body = self.block_statement()?;
 
-        Ok(expr)
+        Ok(Stmt::With {
+            keyword,
+            resource,
+            name,
+            body,
+        })
     }
 
-    fn comparison(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.term()?;
+    // --------------- 表达式解析 ---------------
+    fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.assignment()
+    }
+
+    // 括号、连续赋值、lambda 等嵌套都要经过这里，统一计数
+    fn assignment(&mut self) -> Result<Expr, ParseError> {
+        self.nested(Self::assignment_inner, "Expression too deeply nested")
+    }
 
-        while self.match_tokens(&[
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
+    fn assignment_inner(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.binary()?;
+
+        if self.match_token(TokenType::Equal) {
+            return self.finish_assign(expr);
+        }
+        if self.match_tokens(&[
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
         ]) {
-            let operator = self.previous().clone();
-            let right = self.term()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            return self.finish_compound_assign(expr);
         }
 
         Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.factor()?;
+    // 处理对象属性赋值 obj.x = 5
+    fn finish_assign(&mut self, target: Expr) -> Result<Expr, ParseError> {
+        let equals = self.previous().clone();
+        let value = self.assignment()?;
 
-        while self.match_tokens(&[TokenType::Plus, TokenType::Minus]) {
-            let operator = self.previous().clone();
-            let right = self.factor()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+        match target {
+            Expr::Variable { id, name } => Ok(Expr::Assign {
+                id,
+                name,
+                value: Box::new(value),
+            }),
+            Expr::GetAttribute { object, name } => Ok(Expr::Set {
+                object,
+                name,
+                value: Box::new(value),
+            }),
+            Expr::Index { object, index, bracket } => Ok(Expr::SetIndex {
+                object,
+                index,
+                bracket,
+                value: Box::new(value),
+            }),
+            _ => Err(self.error(&equals, "Invalid assignment target")),
         }
+    }
 
-        Ok(expr)
+    // 复合赋值 a += b：变量脱糖为 a = a + b，属性和下标生成 CompoundAssign
+    fn finish_compound_assign(&mut self, target: Expr) -> Result<Expr, ParseError> {
+        let compound = self.previous().clone();
+        let operator_type = match compound.token_type {
+            TokenType::PlusEqual => TokenType::Plus,
+            TokenType::MinusEqual => TokenType::Minus,
+            TokenType::StarEqual => TokenType::Star,
+            _ => TokenType::Slash,
+        };
+        let operator = Token::new(operator_type, compound.line, compound.lexeme[..1].to_string(), None)
+            .with_column(compound.column);
+        let value = self.assignment()?;
+
+        match target {
+            Expr::Variable { id, name } => Ok(Expr::Assign {
+                id: next_expr_id(),
+                name: name.clone(),
+                value: Box::new(Expr::Binary {
+                    left: Box::new(Expr::Variable { id, name }),
+                    operator,
+                    right: Box::new(value),
+                }),
+            }),
+            Expr::GetAttribute { .. } | Expr::Index { .. } => Ok(Expr::CompoundAssign {
+                target: Box::new(target),
+                operator,
+                value: Box::new(value),
+            }),
+            _ => Err(self.error(&compound, "Invalid assignment target")),
+        }
     }
 
-    fn factor(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.unary()?;
+    // 二元运算：按优先级表用操作数栈和运算符栈归约，所有优先级共用一个栈帧，
+    // 深层嵌套时每层只多占一份 binary 的栈空间。同级运算左结合
+    fn binary(&mut self) -> Result<Expr, ParseError> {
+        let mut operands = vec![self.unary()?];
+        let mut operators: Vec<Token> = Vec::new();
 
-        while self.match_tokens(&[TokenType::Slash, TokenType::Star]) {
-            let operator = self.previous().clone();
-            let right = self.unary()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+        while let Some(precedence) = binary_precedence(&self.peek().token_type) {
+            let operator = self.peek().clone();
+            self.advance();
+            while operators
+                .last()
+                .is_some_and(|top| binary_precedence(&top.token_type) >= Some(precedence))
+            {
+                reduce_binary(&mut operands, &mut operators);
+            }
+            operators.push(operator);
+            operands.push(self.unary()?);
         }
 
-        Ok(expr)
+        while !operators.is_empty() {
+            reduce_binary(&mut operands, &mut operators);
+        }
+        Ok(operands.pop().unwrap())
     }
 
     fn unary(&mut self) -> Result<Expr, ParseError> {
-        if self.match_tokens(&[TokenType::Bang, TokenType::Minus]) {
+        if self.match_tokens(&[TokenType::Bang, TokenType::Minus, TokenType::Tilde, TokenType::TypeOf]) {
             let operator = self.previous().clone();
-            let right = self.unary()?;
+            let right = self.nested(Self::unary, "Expression too deeply nested")?;
             Ok(Expr::Unary {
                 operator,
                 right: Box::new(right),
@@ -470,44 +825,121 @@ impl Parser {
             Ok(Expr::Literal {
                 value: Literal::Nil,
             })
-        } else if self.match_token(TokenType::Number) {
-            Ok(Expr::Literal {
-                value: self.previous().literal.clone().unwrap(),
-            })
-        } else if self.match_token(TokenType::String) {
+        } else if self.match_tokens(&[TokenType::Number, TokenType::String]) {
             Ok(Expr::Literal {
                 value: self.previous().literal.clone().unwrap(),
             })
+        } else if self.match_token(TokenType::LeftBracket) {
+            self.list_literal()
+        } else if self.match_token(TokenType::LeftBrace) {
+            self.map_literal()
         } else if self.match_token(TokenType::LeftParen) {
-            let expr = self.expression()?;
-            self.consume(TokenType::RightParen, "Expect ')' after expression")?;
-            Ok(Expr::Grouping {
-                expression: Box::new(expr),
-            })
+            self.grouping()
         } else if self.match_token(TokenType::Identifier) {
             Ok(Expr::Variable {
+                id: next_expr_id(),
                 name: self.previous().clone(),
             })
         } else if self.match_token(TokenType::This) {
-            if self.allow_this {
-                Ok(Expr::This {
-                    keyword: self.previous().clone(),
-                })
-            } else {
-                // 生成特殊错误消息
-                Err(self.error(
-                    self.previous(),
-                    "Error: Can't use 'this' outside of a class.",
-                ))
-            }
+            self.this_expression()
+        } else if self.match_token(TokenType::Fun) {
+            self.lambda()
+        } else if self.match_token(TokenType::Class) {
+            self.class_expression()
         } else if self.match_token(TokenType::Super) {
-            let keyword = self.previous().clone();
-            self.consume(TokenType::Dot, "Expect '.' after 'super'")?;
-            let method = self.consume_identifier("Expect superclass method name")?;
-            Ok(Expr::Super { keyword, method })
+            self.super_expression()
+        } else {
+            Err(self.error(self.peek(), "Expect expression"))
+        }
+    }
+
+    // 以下各分支从 primary 中拆出，primary 位于每层嵌套的递归路径上，保持它的栈帧较小
+
+    // 列表字面量，允许末尾多一个逗号
+    fn list_literal(&mut self) -> Result<Expr, ParseError> {
+        let mut elements = Vec::new();
+        while !self.check(TokenType::RightBracket) {
+            elements.push(self.expression()?);
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after list elements")?;
+        Ok(Expr::ListLiteral { elements })
+    }
+
+    // 表达式中的花括号是字典字面量，同样允许末尾多一个逗号
+    fn map_literal(&mut self) -> Result<Expr, ParseError> {
+        let brace = self.previous().clone();
+        let mut entries = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            let key = self.expression()?;
+            self.consume(TokenType::Colon, "Expect ':' after map key")?;
+            let value = self.expression()?;
+            entries.push((key, value));
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after map entries")?;
+        Ok(Expr::MapLiteral { entries, brace })
+    }
+
+    fn grouping(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after expression")?;
+        Ok(Expr::Grouping {
+            expression: Box::new(expr),
+        })
+    }
+
+    fn this_expression(&mut self) -> Result<Expr, ParseError> {
+        if self.allow_this {
+            Ok(Expr::This {
+                id: next_expr_id(),
+                keyword: self.previous().clone(),
+            })
         } else {
-            Err(self.error(&self.peek(), "Expect expression"))
+            // 生成特殊错误消息
+            let message = if self.in_static {
+                "Error: Can't use 'this' in a static method."
+            } else {
+                "Error: Can't use 'this' outside of a class."
+            };
+            Err(self.error(self.previous(), message))
+        }
+    }
+
+    // 匿名函数表达式：fun (a, b) { ... }
+    fn lambda(&mut self) -> Result<Expr, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'fun'")?;
+        let (params, has_rest, body) = self.function_rest("function")?;
+        Ok(Expr::Lambda { params, has_rest, body })
+    }
+
+    // 匿名类表达式：class < Base { ... }
+    fn class_expression(&mut self) -> Result<Expr, ParseError> {
+        let (superclass, methods, statics, fields) = self.class_body()?;
+        Ok(Expr::ClassExpr {
+            superclass,
+            methods,
+            statics,
+            fields,
+        })
+    }
+
+    fn super_expression(&mut self) -> Result<Expr, ParseError> {
+        let keyword = self.previous().clone();
+        if self.in_static {
+            return Err(self.error(&keyword, "Error: Can't use 'super' in a static method."));
         }
+        self.consume(TokenType::Dot, "Expect '.' after 'super'")?;
+        let method = self.consume_identifier("Expect superclass method name")?;
+        Ok(Expr::Super {
+            id: next_expr_id(),
+            keyword,
+            method,
+        })
     }
 
     // --------------- 工具方法 ---------------
@@ -530,12 +962,12 @@ impl Parser {
     }
 
     fn consume(&mut self, ttype: TokenType, message: &str) -> Result<Token, ParseError> {
-        if self.check(ttype) {
+        if self.check(ttype.clone()) {
             let token = self.peek().clone();
             self.advance();
             Ok(token)
         } else {
-            Err(self.error(&self.peek(), message))
+            Err(self.expected_error(ttype, message))
         }
     }
 
@@ -545,14 +977,40 @@ impl Parser {
             self.advance(); // 消耗标识符后推进指针
             Ok(token)
         } else {
-            Err(self.error(&self.peek(), msg))
+            Err(self.expected_error(TokenType::Identifier, msg))
+        }
+    }
+
+    // 嵌套层数加一后执行 parse，超过上限时在当前Token处报告 message
+    fn nested<T>(&mut self, parse: fn(&mut Self) -> Result<T, ParseError>, message: &str) -> Result<T, ParseError> {
+        if self.depth >= self.max_depth {
+            let error = self.error(self.peek(), message);
+            // 不再尝试恢复：直接跳到末尾，避免剩余的每一段深层嵌套各报一次错
+            self.current = self.tokens.len();
+            return Err(error);
         }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
     }
 
     fn error(&self, token: &Token, message: &str) -> ParseError {
         ParseError {
             token: token.clone(),
             message: message.to_string(),
+            expected: None,
+            found: None,
+        }
+    }
+
+    // consume 失败：额外记录期望与实际的Token类型，便于工具统一展示
+    fn expected_error(&self, expected: TokenType, message: &str) -> ParseError {
+        let found = self.peek().token_type.clone();
+        ParseError {
+            expected: Some(expected),
+            found: Some(found),
+            ..self.error(self.peek(), message)
         }
     }
 
@@ -569,7 +1027,16 @@ impl Parser {
                 | TokenType::For
                 | TokenType::If
                 | TokenType::While
+                | TokenType::Unless
+                | TokenType::Until
+                | TokenType::Do
+                | TokenType::Switch
+                | TokenType::Try
+                | TokenType::Throw
+                | TokenType::With
                 | TokenType::Print
+                | TokenType::Break
+                | TokenType::Continue
                 | TokenType::Return => return,
                 _ => self.advance(),
             }
@@ -580,6 +1047,13 @@ impl Parser {
         !self.is_at_end() && self.peek().token_type == ttype
     }
 
+    // 向前多看一个token
+    fn check_next(&self, ttype: TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|token| token.token_type == ttype)
+    }
+
     fn advance(&mut self) {
         if !self.is_at_end() {
             self.current += 1;
@@ -587,7 +1061,7 @@ impl Parser {
     }
 
     fn previous(&self) -> &Token {
-        &self.tokens[(self.current - 1).max(0)]
+        &self.tokens[self.current.saturating_sub(1)]
     }
 
     fn peek(&self) -> &Token {
@@ -604,7 +1078,7 @@ impl Parser {
             || self
                 .tokens
                 .get(self.current)
-                .map_or(false, |t| t.token_type == TokenType::Eof)
+                .is_some_and(|t| t.token_type == TokenType::Eof)
     }
 
     fn call(&mut self) -> Result<Expr, ParseError> {
@@ -614,11 +1088,9 @@ impl Parser {
             if self.match_token(TokenType::LeftParen) {
                 expr = self.finish_call(expr)?;
             } else if self.match_token(TokenType::Dot) {
-                let name = self.consume_identifier("Expect property name after '.'")?;
-                expr = Expr::GetAttribute {
-                    object: Box::new(expr),
-                    name,
-                };
+                expr = self.finish_get(expr)?;
+            } else if self.match_token(TokenType::LeftBracket) {
+                expr = self.finish_index(expr)?;
             } else {
                 break;
             }
@@ -627,13 +1099,33 @@ impl Parser {
         Ok(expr)
     }
 
+    fn finish_get(&mut self, object: Expr) -> Result<Expr, ParseError> {
+        let name = self.consume_identifier("Expect property name after '.'")?;
+        Ok(Expr::GetAttribute {
+            object: Box::new(object),
+            name,
+        })
+    }
+
+    fn finish_index(&mut self, object: Expr) -> Result<Expr, ParseError> {
+        let index = self.expression()?;
+        let bracket = self.consume(TokenType::RightBracket, "Expect ']' after index")?;
+        Ok(Expr::Index {
+            object: Box::new(object),
+            index: Box::new(index),
+            bracket,
+        })
+    }
+
     fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
         let mut arguments = Vec::new();
+        let mut spread = Vec::new();
         if !self.check(TokenType::RightParen) {
             loop {
                 if arguments.len() >= 255 {
-                    return Err(self.error(&self.peek(), "Can't have more than 255 arguments"));
+                    return Err(self.error(self.peek(), "Can't have more than 255 arguments"));
                 }
+                spread.push(self.match_token(TokenType::Ellipsis));
                 arguments.push(self.expression()?);
                 if !self.match_token(TokenType::Comma) {
                     break;
@@ -648,6 +1140,37 @@ impl Parser {
             callee: Box::new(callee),
             paren, // 现在类型正确
             arguments,
+            spread,
         })
     }
 }
+
+// 二元运算符的优先级，数值越大结合越紧；不是二元运算符时返回 None。
+// 位运算高于比较（同 Python），a & 1 == 0 按 (a & 1) == 0 解析：
+// | 低于 ^ 低于 & 低于移位，移位低于加减
+fn binary_precedence(token_type: &TokenType) -> Option<u8> {
+    match token_type {
+        TokenType::Or => Some(1),
+        TokenType::And => Some(2),
+        TokenType::BangEqual | TokenType::EqualEqual => Some(3),
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => Some(4),
+        TokenType::Pipe => Some(5),
+        TokenType::Caret => Some(6),
+        TokenType::Ampersand => Some(7),
+        TokenType::LessLess | TokenType::GreaterGreater => Some(8),
+        TokenType::Plus | TokenType::Minus => Some(9),
+        TokenType::Slash | TokenType::Star | TokenType::Percent => Some(10),
+        _ => None,
+    }
+}
+
+// 弹出栈顶运算符和两个操作数，合成一个节点压回操作数栈；and/or 生成 Logical
+fn reduce_binary(operands: &mut Vec<Expr>, operators: &mut Vec<Token>) {
+    let operator = operators.pop().unwrap();
+    let right = Box::new(operands.pop().unwrap());
+    let left = Box::new(operands.pop().unwrap());
+    operands.push(match operator.token_type {
+        TokenType::Or | TokenType::And => Expr::Logical { left, operator, right },
+        _ => Expr::Binary { left, operator, right },
+    });
+}
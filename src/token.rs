@@ -1,23 +1,30 @@
 use serde::Serialize;
 use crate::statement::Stmt;
 use crate::environment::{Environment, RuntimeError};
+use crate::interpreter::Interpreter;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
 use std::rc::Rc;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct LoxFunction {
+    pub name: String,            // 函数名，方法为 类名.方法名，用于性能分析报告
     pub params: Vec<Token>,      // 参数列表
-    pub body: Vec<Stmt>,         // 函数体
+    pub has_rest: bool,          // 最后一个参数为 ...rest 时，多余的实参以列表绑定到它
+    pub body: Rc<Vec<Stmt>>,     // 函数体，同一次声明产生的函数值（及其绑定的方法）共享
     pub closure: Rc<RefCell<Environment>>, // 闭包环境
     pub is_initializer: bool,    // 是否是初始化方法
+    pub is_getter: bool,         // 是否是 getter，通过属性访问时直接调用
 }
 
 #[derive(Debug, Serialize)]
 pub struct LoxClass {
     pub name: String,
     pub environment: Rc<RefCell<Environment>>,
-    pub superclass: Option<Box<LoxClass>>,
+    pub statics: HashMap<String, LoxFunction>, // static 方法
+    pub superclass: Option<Rc<LoxClass>>,
+    pub fields: Vec<Stmt>, // 类体中的字段声明（var 语句），实例化时求值
 }
 
 impl Clone for LoxClass {
@@ -25,24 +32,33 @@ impl Clone for LoxClass {
         Self {
             name: self.name.clone(),
             environment: self.environment.clone(), 
+            statics: self.statics.clone(),
             superclass: self.superclass.clone(),
+            fields: self.fields.clone(),
         }
     }
 }
 
 impl LoxClass {
     pub fn find_method(&self, name: &str) -> Option<Literal> {
-
-        match self.environment.borrow().get(&Token::new_identifier(name.to_string())) {
-            Ok(Literal::FunctionValue(func)) => Some(Literal::FunctionValue(func)),
-            Ok(_) => None,
-            Err(_) => {
+        // 只查找类自身定义的方法，不沿类环境向外查找同名的全局变量
+        match self.environment.borrow().values.get(name) {
+            Some(Literal::FunctionValue(func)) => Some(Literal::FunctionValue(Rc::clone(func))),
+            _ => {
                 // 递归查找超类链
                 self.superclass.as_ref().and_then(|s| s.find_method(name))
             }
         }
     }
 
+    // static 方法同样沿超类链继承
+    pub fn find_static(&self, name: &str) -> Option<LoxFunction> {
+        match self.statics.get(name) {
+            Some(func) => Some(func.clone()),
+            None => self.superclass.as_ref().and_then(|s| s.find_static(name)),
+        }
+    }
+
     pub fn is_subclass_of(&self, other: &LoxClass) -> bool {
         if let Some(ref superclass) = self.superclass {
             if superclass.name == other.name {
@@ -56,50 +72,84 @@ impl LoxClass {
 
 // 为方法调用添加辅助方法
 impl LoxFunction {
-    pub fn bind(&self, instance: &LoxInstance) -> Self {
-        // 创建新环境
-        let new_env = Rc::new(RefCell::new(Environment {
-            values: HashMap::new(),
-            enclosing: Some(Rc::clone(&instance.environment)), 
-        }));
-        
-        // 绑定 this
-        new_env.borrow_mut().define(
-            "this".to_string(),
-            Literal::InstanceValue(instance.clone())
-        );
+    pub fn bind(&self, instance: &Rc<LoxInstance>) -> Self {
+        // 在方法定义时的类环境之上创建新环境，
+        // 这样方法内的 super 指向定义该方法的类的超类，且能访问外层变量
+        let new_env = Environment::new(Some(Rc::clone(&self.closure)));
+
+        // 绑定 this，它是这一层作用域中唯一的局部变量
+        new_env
+            .borrow_mut()
+            .define_local("this", Literal::InstanceValue(Rc::clone(instance)));
 
         // DEBUG1
         // new_env.borrow().check_this_binding("After binding in LoxFunction::bind".to_string());
 
         LoxFunction {
+            name: self.name.clone(),
             params: self.params.clone(),
-            body: self.body.clone(),
+            has_rest: self.has_rest,
+            body: Rc::clone(&self.body),
             closure: new_env, 
             is_initializer: self.is_initializer,
+            is_getter: self.is_getter,
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct LoxInstance {
-    pub class: LoxClass,
+    pub class: Rc<LoxClass>,
     pub environment: Rc<RefCell<Environment>>, // 使用Rc和RefCell共享环境
     pub name: String, // 新增 name 字段
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub enum Literal {
-    StringValue(String),
+    StringValue(Rc<str>),
     NumberValue(f64),
     Boolean(bool),
     Nil,
-    FunctionValue(LoxFunction),
-    ClassValue(LoxClass),
-    InstanceValue(LoxInstance),
+    // 函数、类、实例和原生函数放在 Rc 中，保持 Literal（以及内嵌它的 Token、Expr）尺寸较小
+    FunctionValue(Rc<LoxFunction>),
+    ClassValue(Rc<LoxClass>),
+    InstanceValue(Rc<LoxInstance>),
+    ListValue(Rc<RefCell<Vec<Literal>>>), // 列表按引用共享，修改对所有引用可见
+    MapValue(Rc<RefCell<HashMap<String, Literal>>>), // 字典，键为字符串，同样按引用共享
     None,
     #[serde(skip)]
-    NativeFunctionValue(fn(&[Literal]) -> Result<Literal, RuntimeError>),
+    NativeFunctionValue(Rc<NativeFunction>),
+}
+
+/// 原生函数的实现：可以访问解释器状态，也可以通过 `Interpreter::call` 回调 Lox 函数
+pub type NativeFn = dyn Fn(&mut Interpreter, &[Literal]) -> Result<Literal, RuntimeError>;
+
+/// 原生函数：名字、参数个数和实现
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: Rc<str>,
+    pub arity: Option<usize>, // None 表示参数个数不固定，由实现自行检查
+    pub func: Rc<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &str,
+        arity: Option<usize>,
+        func: impl Fn(&mut Interpreter, &[Literal]) -> Result<Literal, RuntimeError> + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            arity,
+            func: Rc::new(func),
+        }
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
 }
 
 impl Literal {
@@ -110,9 +160,30 @@ impl Literal {
             None
         }
     }
-    pub fn as_class(&self) -> Option<LoxClass> {
+    pub fn as_class(&self) -> Option<Rc<LoxClass>> {
         if let Literal::ClassValue(c) = self {
-            Some(c.clone())
+            Some(Rc::clone(c))
+        } else {
+            None
+        }
+    }
+    pub fn as_number(&self) -> Option<f64> {
+        if let Literal::NumberValue(n) = self {
+            Some(*n)
+        } else {
+            None
+        }
+    }
+    pub fn as_string(&self) -> Option<&str> {
+        if let Literal::StringValue(s) = self {
+            Some(s)
+        } else {
+            None
+        }
+    }
+    pub fn as_bool(&self) -> Option<bool> {
+        if let Literal::Boolean(b) = self {
+            Some(*b)
         } else {
             None
         }
@@ -127,12 +198,188 @@ impl Literal {
             Literal::FunctionValue(_) => "function",
             Literal::ClassValue(_) => "class",
             Literal::InstanceValue(_) => "instance",
+            Literal::ListValue(_) => "list",
+            Literal::MapValue(_) => "map",
             Literal::None => "none",
             Literal::NativeFunctionValue(_) => "nativeFunction",
         }
     }
 }
 
+// 数字的显示形式：整数值不带小数点，其余使用最短的精确小数表示；
+// 数量级过大或过小时改用科学计数法，避免输出几百位数字
+pub(crate) fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".into();
+    }
+    if n.is_infinite() {
+        return if n > 0.0 { "Infinity".into() } else { "-Infinity".into() };
+    }
+    let magnitude = n.abs();
+    if magnitude >= 1e21 || (magnitude != 0.0 && magnitude < 1e-7) {
+        format!("{:e}", n)
+    } else {
+        format!("{}", n)
+    }
+}
+
+/// 值的显示形式，print、字符串拼接、str() 和错误信息共用
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literal::Nil => f.write_str("nil"),
+            Literal::Boolean(b) => write!(f, "{}", b),
+            Literal::NumberValue(n) => f.write_str(&format_number(*n)),
+            Literal::StringValue(s) => f.write_str(s),
+            Literal::FunctionValue(_) => f.write_str("call fn"),
+            Literal::ClassValue(c) => write!(f, "<class {}>", c.name),
+            Literal::InstanceValue(i) => write!(f, "<instance of {}>", i.class.name),
+            Literal::ListValue(_) | Literal::MapValue(_) => f.write_str(&display_collection(self, &mut Vec::new())),
+            Literal::None => f.write_str("nil"), // 合并None和Nil处理
+            Literal::NativeFunctionValue(_) => f.write_str("call native fn"),
+        }
+    }
+}
+
+// 集合中字符串的显示形式：加引号，引号、反斜杠和控制字符按字符串字面量的转义写法输出
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            '\x07' => out.push_str("\\a"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// 列表 [1, "a", [2]] 和字典 {"k": 1} 的显示形式：元素和键中的字符串加引号并转义，字典按键排序；
+// seen 记录正在输出的集合，集合包含自身时输出 [...] 或 {...} 而不是无限递归
+fn display_collection(value: &Literal, seen: &mut Vec<*const ()>) -> String {
+    let (ptr, cycle) = match value {
+        Literal::ListValue(list) => (Rc::as_ptr(list) as *const (), "[...]"),
+        Literal::MapValue(map) => (Rc::as_ptr(map) as *const (), "{...}"),
+        other => return other.to_string(),
+    };
+    if seen.contains(&ptr) {
+        return cycle.into();
+    }
+    seen.push(ptr);
+    let mut element = |e: &Literal| match e {
+        Literal::StringValue(s) => quote(s),
+        other => display_collection(other, seen),
+    };
+    let text = match value {
+        Literal::ListValue(list) => {
+            let parts: Vec<String> = list.borrow().iter().map(&mut element).collect();
+            format!("[{}]", parts.join(", "))
+        }
+        Literal::MapValue(map) => {
+            let map = map.borrow();
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let parts: Vec<String> = keys
+                .into_iter()
+                .map(|k| format!("{}: {}", quote(k), element(&map[k])))
+                .collect();
+            format!("{{{}}}", parts.join(", "))
+        }
+        _ => unreachable!(),
+    };
+    seen.pop();
+    text
+}
+
+/// `==` 的语义，见 literal_eq
+impl PartialEq for Literal {
+    fn eq(&self, other: &Self) -> bool {
+        literal_eq(self, other)
+    }
+}
+
+/// Lox 的 `==` 语义，解释器和外部代码（测试、嵌入方）共用。
+/// 列表和字典按内容逐项比较；实例只与自身相等，分别创建的两个实例即使字段相同也不相等
+pub fn literal_eq(a: &Literal, b: &Literal) -> bool {
+    literal_eq_in(a, b, &mut Vec::new())
+}
+
+// comparing 记录正在比较的容器对，遇到自引用结构时视为相等，避免无限递归
+fn literal_eq_in(a: &Literal, b: &Literal, comparing: &mut Vec<(*const (), *const ())>) -> bool {
+    match (a, b) {
+        // Nil只等于Nil
+        (Literal::Nil, Literal::Nil) => true,
+
+        // 布尔值严格比较
+        (Literal::Boolean(a), Literal::Boolean(b)) => a == b,
+
+        // 数值比较
+        (Literal::NumberValue(a), Literal::NumberValue(b)) => (a - b).abs() < f64::EPSILON,
+
+        // 字符串内容比较
+        (Literal::StringValue(a), Literal::StringValue(b)) => a == b,
+
+        // 函数按身份比较：同一次声明、同一个闭包环境（赋值和传参得到的副本相等）；
+        // 每次取方法都会重新绑定 this，因此 obj.m == obj.m 为 false
+        (Literal::FunctionValue(a), Literal::FunctionValue(b)) => {
+            Rc::ptr_eq(&a.body, &b.body) && Rc::ptr_eq(&a.closure, &b.closure)
+        }
+
+        // 类按身份比较：同一个类的所有副本共享方法环境
+        (Literal::ClassValue(a), Literal::ClassValue(b)) => Rc::ptr_eq(&a.environment, &b.environment),
+
+        // 原生函数按实现比较
+        (Literal::NativeFunctionValue(a), Literal::NativeFunctionValue(b)) => Rc::ptr_eq(&a.func, &b.func),
+
+        // 实例按引用比较：同一个实例共享同一个字段环境
+        (Literal::InstanceValue(a), Literal::InstanceValue(b)) => Rc::ptr_eq(&a.environment, &b.environment),
+
+        // 列表逐个元素比较
+        (Literal::ListValue(a), Literal::ListValue(b)) => {
+            containers_eq(a, b, comparing, |a, b, comparing| {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| literal_eq_in(x, y, comparing))
+            })
+        }
+
+        // 字典的键集合相同且对应的值相等
+        (Literal::MapValue(a), Literal::MapValue(b)) => {
+            containers_eq(a, b, comparing, |a, b, comparing| {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len()
+                    && a.iter().all(|(key, x)| b.get(key).is_some_and(|y| literal_eq_in(x, y, comparing)))
+            })
+        }
+
+        // 其他情况均为不相等
+        _ => false,
+    }
+}
+
+fn containers_eq<T>(
+    a: &Rc<T>,
+    b: &Rc<T>,
+    comparing: &mut Vec<(*const (), *const ())>,
+    eq: impl FnOnce(&Rc<T>, &Rc<T>, &mut Vec<(*const (), *const ())>) -> bool,
+) -> bool {
+    let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+    if Rc::ptr_eq(a, b) || comparing.contains(&pair) {
+        return true;
+    }
+    comparing.push(pair);
+    let equal = eq(a, b, comparing);
+    comparing.pop();
+    equal
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum TokenType {
     // --- 单字符符号 ---
@@ -140,15 +387,25 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
     Plus,
     Semicolon,
+    Colon,
     Slash,
     Star,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // --- 一或两个字符符号 ---
+    LessLess,
+    GreaterGreater,
     Bang,
     BangEqual,
     Equal,
@@ -157,6 +414,13 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+
+    // --- 三个字符符号 ---
+    Ellipsis, // ...，调用时展开列表参数
 
     // --- 字面量 ---
     Identifier,
@@ -180,6 +444,21 @@ pub enum TokenType {
     True,   
     Var,
     While,
+    Unless, // unless (c) 等价于 if (!(c))
+    Until,  // until (c) 等价于 while (!(c))
+    In,     // for (x in collection)
+    Do,     // do body while (c);
+    Switch,
+    Case,
+    Default,
+    Static,
+    Try,
+    Catch,
+    Throw,
+    With,
+    TypeOf,
+    Break,
+    Continue,
 
     // --- 错误类型 ---
     Error,
@@ -192,10 +471,20 @@ pub enum TokenType {
 pub struct Token {
     pub token_type: TokenType,
     pub line: usize,
+    pub column: usize, // 词素首字符所在列（从 1 开始），0 表示未知
     pub lexeme: String,
     pub literal: Option<Literal>,
 }
 
+/// 错误信息中的位置，如 `line 3:12`；列未知时只显示行号
+pub fn format_position(line: usize, column: usize) -> String {
+    if column == 0 {
+        format!("line {}", line)
+    } else {
+        format!("line {}:{}", line, column)
+    }
+}
+
 impl Token {
     pub fn new(
         token_type: TokenType, 
@@ -206,15 +495,27 @@ impl Token {
         Self {
             token_type,
             line,
+            column: 0,
             lexeme,
             literal,
         }
     }
 
+    pub fn with_column(mut self, column: usize) -> Self {
+        self.column = column;
+        self
+    }
+
+    /// 该 Token 在错误信息中的位置
+    pub fn position(&self) -> String {
+        format_position(self.line, self.column)
+    }
+
     pub fn this() -> Self {
         Self {
             token_type: TokenType::This,
             line: 0,
+            column: 0,
             lexeme: "this".into(),
             literal: None,
         }
@@ -224,8 +525,9 @@ impl Token {
         Self {
             token_type: TokenType::Identifier,
             line: 0, // 实际使用时应传入正确的行号
+            column: 0,
             lexeme: name.clone(),
-            literal: Some(Literal::StringValue(name)),
+            literal: Some(Literal::StringValue(name.into())),
         }
     }
 }
\ No newline at end of file
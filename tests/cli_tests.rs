@@ -0,0 +1,356 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// 将源码写入临时文件，返回其路径
+fn write_source(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("lox_cli_{}_{}.lox", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+// 以给定参数运行解释器
+fn run_lox(args: &[&str], name: &str, source: &str) -> Output {
+    let path = write_source(name, source);
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).ok();
+    output
+}
+
+#[test]
+fn check_accepts_valid_program() {
+    let output = run_lox(&["--check"], "check_ok", "print 1 + 2;\nvar a = 3;\n");
+    assert_eq!(output.status.code(), Some(0));
+    // 不执行程序，因此不会有任何输出
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn check_rejects_invalid_program() {
+    let output = run_lox(&["--check"], "check_bad", "print (1 + 2;\n");
+    assert_eq!(output.status.code(), Some(65));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Expect ')' after expression"), "got: {}", stdout);
+}
+
+#[test]
+fn check_reports_scan_errors() {
+    let output = run_lox(&["--check"], "check_scan", "var a = @;\n");
+    assert_eq!(output.status.code(), Some(65));
+
+    // 词法错误之后的语法错误同样报告
+    let output = run_lox(&["--check"], "check_scan_parse", "var a = 1 @ 2;\nprint (1;\n");
+    assert_eq!(output.status.code(), Some(65));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout,
+        "[line 1:11] Unexpected character '@'\n[line 1:13] Error at '2': Expect ';' after variable declaration\n\
+         [line 2:9] Error at ';': Expect ')' after expression\n3 errors\n"
+    );
+}
+
+#[test]
+fn check_reports_resolve_errors() {
+    let output = run_lox(&["--check"], "check_resolve", "{\n  var a = a;\n}\n");
+    assert_eq!(output.status.code(), Some(65));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Can't read local variable in its own initializer."), "got: {}", stdout);
+}
+
+#[test]
+fn strict_comparison_rejects_mixed_types() {
+    let output = run_lox(&[], "cmp_strict", "print 3 < \"4\";\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Cannot order number and string."), "got: {}", stdout);
+}
+
+#[test]
+fn loose_comparison_coerces_numeric_strings() {
+    let source = "print 3 < \"4\";\nprint \" 10 \" > 9;\nprint 2 >= \"2.5\";\n";
+    let output = run_lox(&["--loose-comparison"], "cmp_loose", source);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "true\ntrue\nfalse\n");
+}
+
+#[test]
+fn loose_comparison_rejects_non_numeric_strings() {
+    let output = run_lox(&["--loose-comparison"], "cmp_loose_bad", "print 3 < \"abc\";\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Cannot compare number with non-numeric string 'abc'."), "got: {}", stdout);
+}
+
+#[test]
+fn call_main_runs_main_after_top_level() {
+    let source = "fun main() { print \"in main\"; }\nprint \"top level\";\n";
+    let output = run_lox(&["--call-main"], "main_present", source);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "top level\nin main\n");
+
+    // 不加参数时 main 不会被自动调用
+    let output = run_lox(&[], "main_not_called", source);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "top level\n");
+}
+
+#[test]
+fn call_main_passes_arguments_after_the_file() {
+    let path = write_source("main_args", "fun main(args) { print len(args); print args; }\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .arg("--call-main")
+        .arg(&path)
+        .args(["a", "-b", "3"])
+        .output()
+        .unwrap();
+    fs::remove_file(&path).ok();
+    assert_eq!(output.status.code(), Some(0));
+    // 参数都是字符串，以 - 开头的也原样传入
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n[\"a\", \"-b\", \"3\"]\n");
+
+    // 没有参数时 main(args) 收到空列表，main() 仍以零个参数调用
+    let output = run_lox(&["--call-main"], "main_no_args", "fun main(args) { print args; }\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "[]\n");
+}
+
+#[test]
+fn call_main_without_main_is_a_no_op() {
+    let output = run_lox(&["--call-main"], "main_absent", "print 1;\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+}
+
+#[test]
+fn require_main_errors_when_absent() {
+    let output = run_lox(&["--require-main"], "main_required", "print 1;\n");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Program has no 'main' function."), "got: {}", stdout);
+}
+
+#[test]
+fn max_string_length_stops_runaway_concatenation() {
+    let source = "var s = \"x\";\nwhile (true) {\n  s = s + s;\n}\n";
+    let output = run_lox(&["--max-string-length", "1024"], "max_len", source);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("String length limit exceeded."), "got: {}", stdout);
+}
+
+#[test]
+fn max_string_length_allows_strings_within_limit() {
+    let output = run_lox(&["--max-string-length", "6"], "max_len_ok", "print \"abc\" + \"def\";\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "abcdef\n");
+}
+
+#[cfg(not(feature = "no-std"))]
+#[test]
+fn breakpoint_opens_debugger_with_interactive_debug() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let path = write_source(
+        "breakpoint",
+        "fun f(n) {\n  var doubled = n * 2;\n  breakpoint();\n  return doubled;\n}\nprint f(21);\n",
+    );
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .arg("--interactive-debug")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // 查看局部变量、求值表达式、修改变量后继续执行
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"vars\ndoubled + 1\ndoubled = 100;\ncontinue\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&path).ok();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("doubled = 42\nn = 21\n"), "got: {}", stdout);
+    assert!(stdout.contains("debug> 43\n"), "got: {}", stdout);
+    assert!(stdout.ends_with("100\n"), "got: {}", stdout);
+}
+
+#[test]
+fn repl_echoes_expressions_and_keeps_state() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"var x = 1;\nx = 3;\n1 / 0;\nprint x + 1;\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    // 出错后 REPL 继续运行，之前定义的变量仍然可用
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "> > 3\n> RuntimeError: [line 1] Division by zero.\n> 4\n> "
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[cfg(not(feature = "no-std"))]
+#[test]
+fn read_line_returns_lines_then_nil() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let path = write_source(
+        "read_line",
+        "var name = readLine();\nprint \"hello, \" + name;\nprint readLine();\nprint readLine();\n",
+    );
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // 第二行以 \r\n 结尾，随后输入结束
+    child.stdin.take().unwrap().write_all(b"lox\nsecond line\r\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&path).ok();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello, lox\nsecond line\nnil\n");
+}
+
+#[test]
+fn profile_reports_call_counts_on_stderr() {
+    let source = "fun fib(n) {\n  if (n < 2) return n;\n  return fib(n - 1) + fib(n - 2);\n}\nprint fib(10);\n";
+    let output = run_lox(&["--profile"], "profile", source);
+    // 统计表不混入程序输出
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "55\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let row = stderr.lines().find(|line| line.starts_with("fib ")).unwrap_or_else(|| panic!("got: {}", stderr));
+    // fib(10) 共调用 177 次
+    assert_eq!(row.split_whitespace().nth(1), Some("177"), "got: {}", stderr);
+
+    let output = run_lox(&[], "no_profile", source);
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn uncaught_throw_exits_with_70_on_stderr() {
+    let output = run_lox(&[], "uncaught_throw", "print \"before\";\nthrow \"boom\";\nprint \"after\";\n");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "RuntimeError: boom\n");
+
+    // 数字等非字符串值按 print 的形式输出
+    let output = run_lox(&[], "uncaught_throw_number", "throw 42;\n");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "RuntimeError: 42\n");
+
+    // 内部运行时错误仍然输出到 stdout，退出码 1
+    let output = run_lox(&[], "internal_error", "print 1 / 0;\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stderr.is_empty());
+}
+
+#[cfg(not(feature = "no-std"))]
+#[test]
+fn deterministic_flag_makes_sleep_a_no_op() {
+    let start = std::time::Instant::now();
+    let output = run_lox(&["--deterministic"], "sleep", "sleep(60);\nprint \"done\";\n");
+    assert!(start.elapsed() < std::time::Duration::from_secs(30));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "done\n");
+
+    let output = run_lox(&["--deterministic"], "sleep_negative", "sleep(-0.5);\n");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("sleep() expects a non-negative number of seconds but got -0.5."), "got: {}", stdout);
+}
+
+// 以 -e 运行一段代码
+fn eval_lox(code: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lox")).args(["-e", code]).output().unwrap()
+}
+
+#[test]
+fn eval_prints_trailing_expression_without_semicolon() {
+    let output = eval_lox("1 + 2");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+
+    // 只有末尾省略分号的表达式会输出
+    let output = eval_lox("var a = 2; a * 5; a + 1");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+
+    // 中间的表达式仍然需要分号
+    let output = eval_lox("1 + 2 print 3;");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Expect ';' after expression"), "got: {}", stdout);
+}
+
+#[test]
+fn file_mode_still_requires_trailing_semicolon() {
+    let output = run_lox(&[], "no_semicolon", "print 1;\n1 + 2\n");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Expect ';' after expression"), "got: {}", stdout);
+}
+
+#[test]
+fn tokens_flag_dumps_tokens_without_running() {
+    let output = run_lox(&["--tokens"], "tokens", "var s = \"hi\";\nprint s + 1.5;\n");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "1 | Var | var |");
+    assert_eq!(lines[3], "1 | String | \"hi\" | hi");
+    assert_eq!(lines[8], "2 | Number | 1.5 | 1.5");
+    assert_eq!(lines.last(), Some(&"3 | Eof |  |"));
+
+    let output = run_lox(&["--tokens", "--format", "json"], "tokens_json", "print 1;\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.trim_start().starts_with('['), "got: {}", stdout);
+    assert!(stdout.contains("\"token_type\": \"Print\""), "got: {}", stdout);
+}
+
+#[test]
+fn ast_flag_prints_json_without_running() {
+    let output = run_lox(&["--ast"], "ast", "print 1 + 2;\n");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // 不执行程序，因此没有输出 3
+    assert!(!stdout.lines().any(|line| line == "3"), "got: {}", stdout);
+    assert!(stdout.contains("\"Print\""), "got: {}", stdout);
+    assert!(stdout.contains("\"Binary\""), "got: {}", stdout);
+    assert!(stdout.contains("\"token_type\": \"Plus\""), "got: {}", stdout);
+}
+
+#[test]
+fn unbounded_recursion_reports_stack_overflow() {
+    let source = "fun f(n) {\n  return f(n + 1);\n}\nf(0);\n";
+    let output = run_lox(&[], "stack_overflow", source);
+    // 干净的运行时错误而不是进程崩溃
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "RuntimeError: [line 2] Stack overflow.\n");
+
+    // 默认上限内的递归正常完成，错误可以被 try/catch 捕获
+    let source = "fun down(n) { if (n == 0) return 0; return 1 + down(n - 1); }\nprint down(900);\n\
+                  try { down(5000); } catch (e) { print e; }\n";
+    let output = run_lox(&[], "stack_overflow_caught", source);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "900\nStack overflow.\n");
+
+    let output = run_lox(&["--max-call-depth", "10"], "stack_overflow_limit", "fun down(n) { if (n == 0) return 0; return 1 + down(n - 1); }\nprint down(20);\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "RuntimeError: [line 1] Stack overflow.\n");
+
+    // 过大的上限按解释器线程的栈所能容纳的层数处理，仍然报错而不是崩溃
+    let output = run_lox(&["--max-call-depth", "1000000"], "stack_overflow_huge_limit", "fun f(n) { return f(n + 1); }\nf(0);\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "RuntimeError: [line 1] Stack overflow.\n");
+}
@@ -0,0 +1,108 @@
+use lox::environment::Environment;
+use lox::token::Literal;
+
+#[test]
+fn debug_format_lists_bindings_in_name_order() {
+    let globals = Environment::new(None);
+    globals.borrow_mut().define("zeta".to_string(), Literal::Nil);
+    globals.borrow_mut().define("alpha".to_string(), Literal::Boolean(true));
+
+    let local = Environment::new(Some(globals));
+    for (name, value) in [("c", 3.0), ("a", 1.0), ("b", 2.0), ("d", 4.0)] {
+        local.borrow_mut().define(name.to_string(), Literal::NumberValue(value));
+    }
+
+    let expected = "🛠️  Environment Depth 0:\n\
+                    \x20  🔑 a => NumberValue(1.0)\n\
+                    \x20  🔑 b => NumberValue(2.0)\n\
+                    \x20  🔑 c => NumberValue(3.0)\n\
+                    \x20  🔑 d => NumberValue(4.0)\n\
+                    🛠️  Environment Depth 1:\n\
+                    \x20  🔑 alpha => Boolean(true)\n\
+                    \x20  🔑 zeta => Nil\n";
+    // 多次输出结果相同
+    for _ in 0..5 {
+        assert_eq!(local.borrow().debug_format(0), expected);
+    }
+}
+
+#[test]
+fn iter_lists_only_local_bindings_in_name_order() {
+    let globals = Environment::new(None);
+    globals.borrow_mut().define("outer".to_string(), Literal::Nil);
+
+    let local = Environment::new(Some(globals));
+    for (name, value) in [("b", 2.0), ("c", 3.0), ("a", 1.0)] {
+        local.borrow_mut().define(name.to_string(), Literal::NumberValue(value));
+    }
+
+    let local = local.borrow();
+    let bindings: Vec<(&str, f64)> = local
+        .iter()
+        .map(|(name, value)| (name, value.as_number().unwrap()))
+        .collect();
+    assert_eq!(bindings, [("a", 1.0), ("b", 2.0), ("c", 3.0)]);
+}
+
+#[test]
+fn iter_chain_walks_outwards_with_depths() {
+    let globals = Environment::new(None);
+    globals.borrow_mut().define("zeta".to_string(), Literal::Nil);
+    globals.borrow_mut().define("alpha".to_string(), Literal::Boolean(true));
+
+    let middle = Environment::new(Some(globals));
+    middle.borrow_mut().define("shadowed".to_string(), Literal::NumberValue(1.0));
+
+    let inner = Environment::new(Some(middle));
+    inner.borrow_mut().define("shadowed".to_string(), Literal::NumberValue(2.0));
+    inner.borrow_mut().define("local".to_string(), Literal::NumberValue(3.0));
+
+    let chain: Vec<(usize, String)> = inner
+        .borrow()
+        .iter_chain()
+        .map(|(depth, name, _)| (depth, name))
+        .collect();
+    let expected = [(0, "local"), (0, "shadowed"), (1, "shadowed"), (2, "alpha"), (2, "zeta")];
+    assert_eq!(chain, expected.map(|(depth, name)| (depth, name.to_string())));
+
+    // 同名变量在各层保留各自的值
+    let shadowed: Vec<f64> = inner
+        .borrow()
+        .iter_chain()
+        .filter(|(_, name, _)| name == "shadowed")
+        .map(|(_, _, value)| value.as_number().unwrap())
+        .collect();
+    assert_eq!(shadowed, [2.0, 1.0]);
+}
+
+#[test]
+fn locals_are_read_and_written_by_slot() {
+    use lox::token::Token;
+
+    let globals = Environment::new(None);
+    globals.borrow_mut().define("g".to_string(), Literal::Nil);
+    let outer = Environment::new(Some(globals));
+    outer.borrow_mut().define_local("a", Literal::NumberValue(1.0));
+    outer.borrow_mut().define_local("b", Literal::NumberValue(2.0));
+    let inner = Environment::new(Some(outer));
+    inner.borrow_mut().reserve_local("f");
+    inner.borrow_mut().define_local("c", Literal::NumberValue(3.0));
+
+    let name = Token::new_identifier("x".to_string());
+    let get = |depth, slot| inner.borrow().get_at(depth, slot, &name).ok().and_then(|v| v.as_number());
+    assert_eq!(get(1, 1), Some(2.0));
+    assert_eq!(get(0, 1), Some(3.0));
+    // 预留而未定义的槽位、越界的槽位都视为未定义
+    assert!(inner.borrow().get_at(0, 0, &name).is_err());
+    assert!(inner.borrow().get_at(1, 5, &name).is_err());
+
+    inner.borrow_mut().assign_at(1, 0, &name, Literal::NumberValue(10.0)).unwrap();
+    assert_eq!(get(1, 0), Some(10.0));
+    assert!(inner.borrow_mut().assign_at(0, 0, &name, Literal::Nil).is_err());
+
+    // 按名字查找同样能找到局部变量（调试器使用）
+    let c = Token::new_identifier("c".to_string());
+    assert_eq!(inner.borrow().get(&c).unwrap().as_number(), Some(3.0));
+    let names: Vec<String> = inner.borrow().iter_chain().map(|(_, name, _)| name).collect();
+    assert_eq!(names, ["c", "a", "b", "g"]);
+}
@@ -0,0 +1,436 @@
+use lox::interpreter::Interpreter;
+use lox::UnifiedError;
+
+#[test]
+fn interpret_source_keeps_state_between_snippets() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .interpret_source("var base = 40; fun add(n) { return base + n; }")
+        .unwrap();
+    // 第二段代码使用第一段的定义
+    interpreter
+        .interpret_source("if (add(2) != 42) throw \"wrong result\";")
+        .unwrap();
+}
+
+#[test]
+fn interpret_source_reports_each_phase() {
+    let mut interpreter = Interpreter::new();
+    assert!(matches!(interpreter.interpret_source("var a = @;"), Err(UnifiedError::Scan(_))));
+    assert!(matches!(interpreter.interpret_source("print (1;"), Err(UnifiedError::Parse(_))));
+    assert!(matches!(interpreter.interpret_source("print 1 / 0;"), Err(UnifiedError::Runtime(_))));
+}
+
+#[test]
+fn run_is_stateless() {
+    lox::run("var a = 1;").unwrap();
+    assert!(matches!(lox::run("print a;"), Err(UnifiedError::Runtime(_))));
+}
+
+#[test]
+fn string_plus_number_is_an_error_by_default() {
+    let mut interpreter = Interpreter::new();
+    let err = interpreter.interpret_source("var s = \"count: \" + 5;").unwrap_err();
+    assert!(err.to_string().contains("Operands must be two numbers or two strings."));
+}
+
+#[test]
+fn string_plus_coerces_when_enabled() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_string_plus_coerces(true);
+    interpreter
+        .interpret_source(
+            "if (\"count: \" + 5 != \"count: 5\") throw \"number\";
+             if (true + \"!\" != \"true!\") throw \"boolean\";
+             if (\"n\" + 1.5 != \"n1.5\") throw \"float\";",
+        )
+        .unwrap();
+    // 数字与 nil 等其他类型仍然报错
+    assert!(interpreter.interpret_source("var s = \"a\" + nil;").is_err());
+}
+
+#[test]
+fn run_collecting_captures_output_up_to_the_error() {
+    let statements = lox::parse("print \"before\"; print 1 + 2; print 1 / 0; print \"after\";").unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.resolve(&statements).unwrap();
+
+    let (result, output) = interpreter.run_collecting(&statements);
+    assert!(result.unwrap_err().to_string().contains("Division by zero."));
+    assert_eq!(output, "before\n3\n");
+
+    // 捕获结束后恢复原输出目标，再次捕获时缓冲区是全新的
+    let (result, output) = interpreter.run_collecting(&lox::parse("print \"again\";").unwrap());
+    assert!(result.is_ok());
+    assert_eq!(output, "again\n");
+}
+
+#[test]
+fn run_collecting_resolves_locals() {
+    // 直接传入解析器的输出，局部变量同样按作用域查找
+    let mut interpreter = Interpreter::new();
+    let statements = lox::parse("var a = \"global\"; { var a = 1; print a; } fun f() { var b = 2; return b; } print f();").unwrap();
+    let (result, output) = interpreter.run_collecting(&statements);
+    assert!(result.is_ok());
+    assert_eq!(output, "1\n2\n");
+
+    let (result, output) = interpreter.run_collecting(&lox::parse("print 1; { var c = c; }").unwrap());
+    assert!(result.unwrap_err().to_string().contains("Can't read local variable in its own initializer"));
+    assert_eq!(output, "");
+}
+
+#[test]
+fn write_prints_without_newline() {
+    let statements = lox::parse("write(\"a\"); write(\"b\"); write(1.5); write(nil);").unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.resolve(&statements).unwrap();
+
+    let (result, output) = interpreter.run_collecting(&statements);
+    assert!(result.is_ok());
+    assert_eq!(output, "ab1.5nil");
+}
+
+#[test]
+fn modulo_on_integers_and_fractions() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .interpret_source(
+            "if (10 % 3 != 1) throw \"integer\";
+             if (-7 % 3 != -1) throw \"negative\";
+             if (5.5 % 2 != 1.5) throw \"fraction\";
+             if (2 + 7 % 4 * 2 != 8) throw \"precedence\";",
+        )
+        .unwrap();
+}
+
+#[test]
+fn modulo_by_zero_is_an_error() {
+    let err = lox::run("print 5 % 0;").unwrap_err();
+    assert!(err.to_string().contains("Modulo by zero."), "got: {}", err);
+}
+
+#[test]
+fn bitwise_operands_must_fit_in_64_bits() {
+    // 2^63 = i64::MAX + 1，已超出范围；-2^63 恰好是最小值
+    for source in [
+        "print 9223372036854775808 & 1;",
+        "print 18446744073709551616 | 0;",
+        "print ~-9223372036854777856;",
+        "print 9223372036854775807 * 4 >> 1;",
+    ] {
+        let err = lox::run(source).unwrap_err();
+        assert!(err.to_string().contains("Operand too large for bitwise operation."), "{}: {}", source, err);
+    }
+    assert!(lox::run("print -9223372036854775808 & 1;").is_ok());
+}
+
+#[test]
+fn bitwise_operands_must_be_integers() {
+    let err = lox::run("print 1.5 | 0;").unwrap_err();
+    assert!(err.to_string().contains("Operand of '|' must be an integer but got 1.5."), "got: {}", err);
+    let err = lox::run("print \"a\" & 1;").unwrap_err();
+    assert!(err.to_string().contains("Operand of '&' must be a number but got string."), "got: {}", err);
+    let err = lox::run("print 1 << 64;").unwrap_err();
+    assert!(err.to_string().contains("Shift amount must be between 0 and 63 but got 64."), "got: {}", err);
+}
+
+#[test]
+fn strings_cannot_be_assigned_through_an_index() {
+    let err = lox::run("var s = \"ab\";\ns[0] = \"x\";").unwrap_err();
+    assert!(err.to_string().contains("[line 2] Strings are immutable."), "got: {}", err);
+}
+
+#[test]
+fn chained_index_assignment_into_lists_maps_and_fields() {
+    let statements = lox::parse(
+        "var grid = [[0, 0], [0, 0]];\n\
+         grid[1][0] = 7;\n\
+         grid[0][1] += 2;\n\
+         print grid;\n\
+         class Bag { init() { this.items = [1, 2]; this.tags = {\"a\": [0]}; } }\n\
+         var bag = Bag();\n\
+         bag.items[1] = 20;\n\
+         bag.tags[\"a\"][0] = \"x\";\n\
+         bag.tags[\"b\"] = [];\n\
+         print bag.items;\n\
+         print bag.tags;\n\
+         var calls = 0;\n\
+         fun zero() { calls = calls + 1; return 0; }\n\
+         grid[zero()][zero()] += 1;\n\
+         print calls;",
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.resolve(&statements).unwrap();
+
+    let (result, output) = interpreter.run_collecting(&statements);
+    assert!(result.is_ok());
+    // 复合赋值时对象和下标表达式都只求值一次
+    assert_eq!(output, "[[0, 2], [7, 0]]\n[1, 20]\n{\"a\": [\"x\"], \"b\": []}\n2\n");
+}
+
+#[cfg(not(feature = "no-std"))]
+#[test]
+fn sleep_validates_duration_and_skips_waiting_when_deterministic() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_deterministic(true);
+    let start = std::time::Instant::now();
+    interpreter.interpret_source("sleep(30); sleep(0.5); sleep(0);").unwrap();
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+
+    // 确定性模式下同样检查参数
+    for (source, message) in [
+        ("sleep(-1);", "sleep() expects a non-negative number of seconds but got -1."),
+        ("sleep(\"1\");", "sleep() expects a number but got string."),
+    ] {
+        let err = interpreter.interpret_source(source).unwrap_err();
+        assert!(err.to_string().contains(message), "{}: {}", source, err);
+    }
+}
+
+#[cfg(not(feature = "no-std"))]
+#[test]
+fn sleep_advances_clock_millis() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .interpret_source(
+            "var start = clockMillis(); sleep(0.01); var elapsed = clockMillis() - start;\n\
+             if (start % 1 != 0) throw \"clockMillis() should be an integer\";\n\
+             if (elapsed < 10 or elapsed > 5000) throw elapsed;",
+        )
+        .unwrap();
+}
+
+#[cfg(feature = "no-std")]
+#[test]
+fn no_std_leaves_out_system_natives() {
+    for name in ["clock", "clockMillis", "readLine", "breakpoint", "sleep"] {
+        let err = lox::run(&format!("print {};", name)).unwrap_err();
+        assert!(err.to_string().contains("Undefined variable"), "got: {}", err);
+    }
+    // 语言核心和其余内置函数不受影响
+    lox::run("if (!starts_with(\"lox\", \"lo\") or 7 % 4 != 3) throw \"core\";").unwrap();
+}
+
+#[test]
+fn repl_mode_echoes_top_level_expression_statements() {
+    let source = "var x = 1; x = 3; x + 1; fun f() { 5; } f(); { x; }";
+
+    let mut script = Interpreter::new();
+    let statements = lox::parse(source).unwrap();
+    script.resolve(&statements).unwrap();
+    let (result, output) = script.run_collecting(&statements);
+    result.unwrap();
+    assert_eq!(output, "");
+
+    // 同样的语句在 REPL 模式下回显顶层表达式（赋值也是表达式），块内和函数体内的不回显
+    let mut repl = Interpreter::new();
+    repl.set_repl_mode(true);
+    let statements = lox::parse(source).unwrap();
+    repl.resolve(&statements).unwrap();
+    let (result, output) = repl.run_collecting(&statements);
+    result.unwrap();
+    assert_eq!(output, "3\n4\nnil\n");
+}
+
+#[test]
+fn redeclaration_allowed_globally_but_not_in_blocks() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .interpret_source("var a = 1; var a = a + 1; if (a != 2) throw \"global\";")
+        .unwrap();
+    // REPL 中多次输入同一个声明也不会出错
+    interpreter.interpret_source("var a = 3;").unwrap();
+
+    // 块内的重复声明在变量解析阶段报告，带行号和列号，一条语句也不执行
+    for source in ["{ var b = 1; var b = 2; }", "fun f() { var c = 1; var c = 2; } f();", "print 1; { var d; var d; }"] {
+        let err = interpreter.interpret_source(source).unwrap_err();
+        assert!(matches!(err, UnifiedError::Parse(_)), "{}: {}", source, err);
+        assert!(err.to_string().contains("Already a variable with name"), "{}: {}", source, err);
+    }
+    // 提前定义的块内函数名不算声明
+    interpreter.interpret_source("{ var g = 1; fun g() {} }").unwrap();
+}
+
+#[test]
+fn tight_loop_over_slot_indexed_locals() {
+    // 局部变量按槽位访问；一百万次自增在函数内的局部变量上完成
+    let statements = lox::parse(
+        "fun run() {
+           var count = 0;
+           for (var i = 0; i < 1000000; i = i + 1) {
+             count = count + 1;
+           }
+           return count;
+         }
+         print run();",
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.resolve(&statements).unwrap();
+    let (result, output) = interpreter.run_collecting(&statements);
+    result.unwrap();
+    assert_eq!(output, "1000000\n");
+}
+
+#[test]
+fn call_depth_limit_is_configurable_and_resets_after_overflow() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_max_call_depth(5);
+    interpreter
+        .interpret_source("fun down(n) { if (n == 0) return 0; return 1 + down(n - 1); }")
+        .unwrap();
+
+    let err = interpreter.interpret_source("down(5);").unwrap_err();
+    assert!(err.to_string().contains("Stack overflow."), "got: {}", err);
+    // 出错后调用层数归零，之后的调用不受影响
+    interpreter.interpret_source("if (down(4) != 4) throw \"depth\";").unwrap();
+}
+
+#[test]
+fn default_call_depth_fits_in_16mb_stack() {
+    // 调试构建下默认上限约需 11MB 栈：上限必须在栈耗尽之前触发
+    let handle = std::thread::Builder::new()
+        .stack_size(16 * 1024 * 1024)
+        .spawn(|| {
+            for source in [
+                "fun f(n){return f(n+1);} f(0);",
+                "fun f(n) { if (true) { var x = 1 + f(n + 1); } } f(0);",
+                "fun f(n) { for (var i = 0; i < 1; i = i + 1) { while (true) { f(n + 1); } } } f(0);",
+                "class A { m(n) { return this.m(n + 1); } } A().m(0);",
+            ] {
+                let err = lox::run(source).unwrap_err();
+                assert!(err.to_string().contains("Stack overflow."), "{}: {}", source, err);
+            }
+        })
+        .unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn long_else_if_chain_runs() {
+    let mut source = String::from("fun classify(n) {\n  if (n == 0) return 0;\n");
+    for i in 1..120 {
+        source.push_str(&format!("  else if (n == {i}) return {i};\n"));
+    }
+    source.push_str("  else return -1;\n}\n");
+    source.push_str("if (classify(119) != 119 or classify(120) != -1) throw \"wrong branch\";");
+    lox::run(&source).unwrap();
+}
+
+#[test]
+fn property_errors_report_the_line() {
+    for (source, message) in [
+        ("var x = nil;\nprint x.foo;", "[line 2] Only instances have attributes."),
+        ("class A {}\nA().foo();", "[line 2] Undefined property 'foo'."),
+        ("class A {}\nprint A().foo;", "[line 2] Undefined property 'foo'."),
+        ("var x = 1;\nx.y = 2;", "[line 2] Only instances can have fields."),
+        ("var x = 1;\nx.y += 2;", "[line 2] Only instances can have fields."),
+        ("class A {}\nclass B < A {\n  m() { return super.nope; }\n}\nB().m();", "[line 3] Undefined property 'nope'."),
+    ] {
+        let err = lox::run(source).unwrap_err();
+        assert_eq!(err.to_string(), format!("RuntimeError: {}", message), "{}", source);
+    }
+}
+
+#[test]
+fn math_natives_validate_arguments() {
+    for (source, message) in [
+        ("sqrt(-1);", "sqrt() of negative number -1."),
+        ("sqrt(\"4\");", "sqrt() expects a number but got string."),
+        ("pow(2);", "Expected 2 arguments but got 1."),
+        ("pow(2, nil);", "pow() expects a number but got nil."),
+        ("abs(true);", "abs() expects a number but got boolean."),
+        ("floor();", "Expected 1 arguments but got 0."),
+        ("ceil([1]);", "ceil() expects a number but got list."),
+        ("round(1, 2);", "Expected 1 arguments but got 2."),
+    ] {
+        let err = lox::run(source).unwrap_err();
+        assert!(err.to_string().contains(message), "{}: {}", source, err);
+    }
+}
+
+#[test]
+fn seed_random_makes_sequences_reproducible() {
+    let source = "seedRandom(42);\n\
+                  var out = [];\n\
+                  for (var i = 0; i < 5; i = i + 1) push(out, random());\n\
+                  for (var i = 0; i < 5; i = i + 1) push(out, randomInt(1, 6));\n\
+                  print out;";
+    let run = || {
+        let statements = lox::parse(source).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.resolve(&statements).unwrap();
+        let (result, output) = interpreter.run_collecting(&statements);
+        result.unwrap();
+        output
+    };
+    let first = run();
+    assert_eq!(first, run());
+
+    // 取值范围：random() 在 [0, 1) 内，randomInt 包含两端
+    lox::run(
+        "for (var i = 0; i < 1000; i = i + 1) {\n\
+           var f = random();\n\
+           if (f < 0 or f >= 1) throw f;\n\
+           var n = randomInt(-2, 2);\n\
+           if (n < -2 or n > 2 or n % 1 != 0) throw n;\n\
+         }\n\
+         if (randomInt(7, 7) != 7) throw \"single\";",
+    )
+    .unwrap();
+
+    for (source, message) in [
+        ("randomInt(3, 1);", "randomInt() lower bound 3 is greater than upper bound 1."),
+        ("randomInt(1.5, 2);", "randomInt() expects an integer but got 1.5."),
+        ("seedRandom(\"a\");", "seedRandom() expects a number but got string."),
+        ("random(1);", "Expected 0 arguments but got 1."),
+    ] {
+        let err = lox::run(source).unwrap_err();
+        assert!(err.to_string().contains(message), "{}: {}", source, err);
+    }
+}
+
+#[test]
+#[allow(clippy::result_large_err)] // RuntimeError 较大，与库中的约定相同
+fn natives_can_keep_state_and_call_back_into_lox() {
+    use lox::token::{Literal, NativeFunction};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let calls = Rc::new(Cell::new(0));
+    let counter = Rc::clone(&calls);
+    let mut interpreter = Interpreter::new();
+    // 对列表的每个元素调用两次回调，返回最后一次的结果之和
+    interpreter.register_native(NativeFunction::new("sumTwice", Some(2), move |interpreter, args| {
+        let Literal::ListValue(list) = &args[0] else {
+            return Err(lox::environment::RuntimeError::Runtime("sumTwice() expects a list.".into()));
+        };
+        let mut sum = 0.0;
+        for item in list.borrow().clone() {
+            interpreter.call(&args[1], vec![item.clone()])?;
+            if let Literal::NumberValue(n) = interpreter.call(&args[1], vec![item])? {
+                sum += n;
+            }
+            counter.set(counter.get() + 1);
+        }
+        Ok(Literal::NumberValue(sum))
+    }));
+
+    let statements = lox::parse(
+        "var seen = 0;\n\
+         print sumTwice([1, 2, 3], fun (x) { seen = seen + 1; return x * 10; });\n\
+         print seen;\n\
+         sumTwice([1], fun () { return 0; });",
+    )
+    .unwrap();
+    interpreter.resolve(&statements).unwrap();
+    let (result, output) = interpreter.run_collecting(&statements);
+    assert_eq!(output, "60\n6\n");
+    assert_eq!(calls.get(), 3);
+    // 回调的参数个数错误报告在原生函数的调用处
+    assert!(result.unwrap_err().to_string().contains("[line 4] Expected 0 arguments but got 1."));
+
+    let err = interpreter.interpret_source("sumTwice([]);").unwrap_err();
+    assert!(err.to_string().contains("Expected 2 arguments but got 1."), "got: {}", err);
+}
@@ -0,0 +1,241 @@
+use lox::expr::Expr;
+use lox::scanner::Scanner;
+use lox::statement::Stmt;
+use lox::syntaxer::{render_parse_errors, ParseError, Parser};
+use lox::token::{Token, TokenType};
+
+fn parse_error(source: &str) -> ParseError {
+    let tokens = Scanner::new(source).scan_tokens().unwrap();
+    Parser::new(tokens).parse().unwrap_err().remove(0)
+}
+
+#[test]
+fn missing_paren_reports_expected_and_found() {
+    let err = parse_error("print (1 + 2;");
+    assert_eq!(err.expected, Some(TokenType::RightParen));
+    assert_eq!(err.found, Some(TokenType::Semicolon));
+    assert_eq!(err.to_string(), "Error at ';': Expect ')' after expression");
+}
+
+#[test]
+fn non_consume_errors_have_no_expected_token() {
+    let err = parse_error("print ;");
+    assert_eq!(err.expected, None);
+    assert_eq!(err.found, None);
+}
+
+#[test]
+fn lox_parse_returns_ast_without_running() {
+    let ast = lox::parse("var a = 1; print a / 0;").unwrap();
+    assert_eq!(ast.len(), 2);
+}
+
+#[test]
+fn lox_parse_collects_all_errors() {
+    let errors = lox::parse("print (1;\nvar = 2;\nvar ok = 3;\nprint \"x\" @;\n").unwrap_err();
+    // 第 4 行的词法错误与前两行的语法错误按位置排列
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[0].message, "Expect ')' after expression");
+    assert_eq!(errors[1].message, "Expect variable name");
+    assert_eq!(errors[2].token.token_type, TokenType::Error);
+
+    // 同一行内按列排列：缺少右括号的语法错误在其后 @ 的词法错误之前
+    let errors = lox::parse("print (1; @").unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].message, "Expect ')' after expression");
+    assert_eq!(errors[1].token.token_type, TokenType::Error);
+}
+
+fn error_at_line(line: usize, message: &str) -> ParseError {
+    ParseError {
+        token: Token::new(TokenType::Semicolon, line, ";".to_string(), None),
+        message: message.to_string(),
+        expected: None,
+        found: None,
+    }
+}
+
+#[test]
+fn render_parse_errors_sorts_by_line_and_counts() {
+    let errors = vec![
+        error_at_line(7, "third"),
+        error_at_line(2, "first"),
+        error_at_line(4, "second"),
+    ];
+    assert_eq!(
+        render_parse_errors(&errors),
+        "[line 2] Error at ';': first\n\
+         [line 4] Error at ';': second\n\
+         [line 7] Error at ';': third\n\
+         3 errors"
+    );
+}
+
+#[test]
+fn render_parse_errors_uses_singular_summary() {
+    let rendered = render_parse_errors(&[error_at_line(1, "only")]);
+    assert!(rendered.ends_with("\n1 error"), "got: {}", rendered);
+}
+
+#[test]
+fn unless_and_until_desugar_to_negated_if_and_while() {
+    let ast = lox::parse("unless (a) print 1; else print 2;\nuntil (b) print 3;").unwrap();
+    let negated = |expr: &Expr| {
+        matches!(expr, Expr::Unary { operator, right }
+            if operator.token_type == TokenType::Bang && matches!(**right, Expr::Variable { .. }))
+    };
+    match &ast[0] {
+        Stmt::If { condition, else_branch, .. } => {
+            assert!(negated(condition), "got: {:?}", condition);
+            assert!(else_branch.is_some());
+        }
+        other => panic!("expected if, got {:?}", other),
+    }
+    match &ast[1] {
+        Stmt::While { condition, .. } => {
+            assert!(negated(condition), "got: {:?}", condition);
+            // 取反运算符使用 until 关键字的行号，运行时错误仍指向原位置
+            if let Expr::Unary { operator, .. } = condition {
+                assert_eq!(operator.line, 2);
+            }
+        }
+        other => panic!("expected while, got {:?}", other),
+    }
+}
+
+#[test]
+fn parse_repl_turns_trailing_expression_into_print() {
+    let tokens = Scanner::new("var a = 1; a + 2").scan_tokens().unwrap();
+    let statements = Parser::new(tokens).parse_repl().unwrap();
+    assert!(matches!(statements.last(), Some(Stmt::Print { .. })));
+
+    // 普通解析入口仍然要求分号
+    let err = parse_error("var a = 1; a + 2");
+    assert_eq!(err.expected, Some(TokenType::Semicolon));
+}
+
+#[test]
+fn for_in_parses_to_for_each() {
+    let ast = lox::parse("for (x in [1, 2]) { if (x > 1) break; print x; }").unwrap();
+    match &ast[0] {
+        Stmt::ForEach { name, iterable, .. } => {
+            assert_eq!(name.lexeme, "x");
+            assert!(matches!(iterable, Expr::ListLiteral { .. }));
+        }
+        other => panic!("expected ForEach, got {:?}", other),
+    }
+
+    // C 风格的 for 不受影响
+    let ast = lox::parse("for (x = 0; x < 1; x = x + 1) print x;").unwrap();
+    assert!(matches!(ast[0], Stmt::For { .. }));
+}
+
+#[test]
+fn switch_collects_cases_and_default() {
+    let ast = lox::parse("switch (x) { case 1: print 1; print 2; case \"a\": default: print 3; }").unwrap();
+    match &ast[0] {
+        Stmt::Switch { cases, default, .. } => {
+            assert_eq!(cases.len(), 2);
+            assert_eq!(cases[0].1.len(), 2);
+            assert!(cases[1].1.is_empty());
+            assert_eq!(default.as_ref().map(Vec::len), Some(1));
+        }
+        other => panic!("expected Switch, got {:?}", other),
+    }
+
+    let err = parse_error("switch (x) { default: print 1; default: print 2; }");
+    assert_eq!(err.message, "Multiple 'default' labels in switch");
+}
+
+#[test]
+fn deeply_nested_code_reports_parse_error() {
+    // 与主线程默认的 8MB 栈相同：嵌套上限必须在栈耗尽之前触发
+    let handle = std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(|| {
+            let nested_error = |source: String| {
+                let errors = lox::parse(&source).unwrap_err();
+                assert_eq!(errors.len(), 1);
+                errors[0].to_string()
+            };
+
+            let depth = 10_000;
+            assert_eq!(
+                nested_error(format!("print {}1{};", "(".repeat(depth), ")".repeat(depth))),
+                "Error at '(': Expression too deeply nested"
+            );
+            assert_eq!(
+                nested_error(format!("print {}1;", "-".repeat(depth))),
+                "Error at '-': Expression too deeply nested"
+            );
+
+            // 代码块、控制流语句和函数体的嵌套同样计数
+            assert_eq!(
+                nested_error("{".repeat(100_000)),
+                "Error at '{': Statement too deeply nested"
+            );
+            assert_eq!(
+                nested_error(format!("{}print 1;", "if (true) ".repeat(depth))),
+                "Error at 'true': Expression too deeply nested"
+            );
+            assert_eq!(
+                nested_error(format!("{}print 1;", "while (false) ".repeat(depth))),
+                "Error at 'false': Expression too deeply nested"
+            );
+            assert_eq!(
+                nested_error("fun f() { ".repeat(depth)),
+                "Error at 'fun': Statement too deeply nested"
+            );
+
+            // 上限以内的嵌套正常解析，上限可以调整
+            let source = format!("print {}1{};", "(".repeat(400), ")".repeat(400));
+            assert!(lox::parse(&source).is_ok());
+            let mut parser = Parser::new(Scanner::new(&source).scan_tokens().unwrap());
+            parser.set_max_depth(20);
+            assert!(parser.parse().is_err());
+        })
+        .unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn else_if_chains_do_not_count_as_nesting() {
+    // else if 分支是并列关系，分支数远超嵌套上限也能解析
+    let mut source = String::from("if (x == 0) print 0;");
+    for i in 1..2000 {
+        source.push_str(&format!(" else if (x == {i}) print {i};"));
+    }
+    source.push_str(" else unless (x) print -1; else print -2;");
+    let mut parser = Parser::new(Scanner::new(&source).scan_tokens().unwrap());
+    parser.set_max_depth(20);
+    assert!(parser.parse().is_ok());
+}
+
+#[test]
+fn spread_arguments_are_flagged_on_the_call() {
+    let ast = lox::parse("f(1, ...xs, 2);").unwrap();
+    match &ast[0] {
+        Stmt::Expression {
+            expression: Expr::Call { arguments, spread, .. },
+        } => {
+            assert_eq!(arguments.len(), 3);
+            assert_eq!(spread, &[false, true, false]);
+        }
+        other => panic!("expected call, got {:?}", other),
+    }
+}
+
+#[test]
+fn rest_parameter_must_be_last() {
+    let ast = lox::parse("fun f(a, ...rest) {}").unwrap();
+    match &ast[0] {
+        Stmt::Function { params, has_rest, .. } => {
+            assert_eq!(params.len(), 2);
+            assert!(has_rest);
+        }
+        other => panic!("expected function, got {:?}", other),
+    }
+
+    let err = parse_error("fun f(...rest, b) {}");
+    assert_eq!(err.to_string(), "Error at ',': Rest parameter must be last");
+}
@@ -0,0 +1,275 @@
+use lox::assert_token;
+use lox::scanner::Scanner;
+use lox::token::TokenType;
+
+#[test]
+fn block_comment_is_skipped() {
+    let mut scanner = Scanner::new("1 /* comment */ + 2");
+    assert_token!(scanner, TokenType::Number);
+    assert_token!(scanner, TokenType::Plus);
+    assert_token!(scanner, TokenType::Number);
+    assert_token!(scanner, TokenType::Eof);
+}
+
+#[test]
+fn nested_block_comment_is_fully_consumed() {
+    let mut scanner = Scanner::new("/* outer /* inner */ still comment */ var\n/* a\n/* b */\n*/ x");
+    assert_token!(scanner, TokenType::Var);
+    let token = scanner.scan_token();
+    assert_eq!(token.token_type, TokenType::Identifier);
+    // 注释内的换行也计入行号
+    assert_eq!(token.line, 4);
+}
+
+#[test]
+fn unterminated_block_comment_is_an_error() {
+    let mut scanner = Scanner::new("var a; /* outer /* inner */\n");
+    assert_token!(scanner, TokenType::Var);
+    assert_token!(scanner, TokenType::Identifier);
+    assert_token!(scanner, TokenType::Semicolon);
+    let token = scanner.scan_token();
+    assert_eq!(token.token_type, TokenType::Error);
+    assert!(token.lexeme.contains("Unterminated block comment"), "got: {}", token.lexeme);
+}
+
+#[test]
+fn scan_tokens_reports_every_error_with_its_line() {
+    let errors = Scanner::new("var a = #;\nprint a $ 2;\nvar s = \"never\nclosed;\n")
+        .scan_tokens()
+        .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            "[line 1:9] Unexpected character '#'",
+            "[line 2:9] Unexpected character '$'",
+            // 未闭合的字符串报告其起始位置
+            "[line 3:9] Unterminated string",
+        ]
+    );
+}
+
+#[test]
+fn tokens_on_the_same_line_carry_columns() {
+    let mut scanner = Scanner::new("var answer = 42;");
+    let columns: Vec<usize> = (0..5).map(|_| scanner.scan_token().column).collect();
+    assert_eq!(columns, vec![1, 5, 12, 14, 16]);
+}
+
+#[test]
+fn column_restarts_after_newline() {
+    // 双字符运算符、字符串和注释都按字符计列
+    let mut scanner = Scanner::new("a >= \"hi\" // note\n  /* c */ b\n\tc");
+    let positions: Vec<(usize, usize)> = (0..5)
+        .map(|_| {
+            let token = scanner.scan_token();
+            (token.line, token.column)
+        })
+        .collect();
+    assert_eq!(positions, vec![(1, 1), (1, 3), (1, 6), (2, 11), (3, 2)]);
+}
+
+#[test]
+fn compound_assignment_operators() {
+    let tokens = Scanner::new("a += 1 -= 2 *= 3 /= 4 / 5").scan_tokens().unwrap();
+    let types: Vec<TokenType> = tokens.iter().map(|t| t.token_type.clone()).collect();
+    assert_eq!(
+        types,
+        [
+            TokenType::Identifier,
+            TokenType::PlusEqual,
+            TokenType::Number,
+            TokenType::MinusEqual,
+            TokenType::Number,
+            TokenType::StarEqual,
+            TokenType::Number,
+            TokenType::SlashEqual,
+            TokenType::Number,
+            TokenType::Slash,
+            TokenType::Number,
+            TokenType::Eof,
+        ]
+    );
+}
+
+#[test]
+fn underscores_separate_digits_in_numbers() {
+    use lox::token::Literal;
+
+    for (source, expected) in [("1_000_000", 1_000_000.0), ("1.234_567", 1.234_567), ("1_2.3_4", 12.34)] {
+        let token = Scanner::new(source).scan_token();
+        assert_eq!(token.token_type, TokenType::Number, "{}", source);
+        assert!(matches!(token.literal, Some(Literal::NumberValue(n)) if n == expected), "{}", source);
+    }
+
+    // 以下划线开头的是标识符，不是数字
+    let mut scanner = Scanner::new("_1");
+    assert_token!(scanner, TokenType::Identifier);
+}
+
+#[test]
+fn misplaced_digit_separators_are_errors() {
+    for (source, reason) in [
+        ("1__0", "doubled digit separator"),
+        ("1_", "trailing digit separator"),
+        ("1.5_", "trailing digit separator"),
+        ("1_.5", "digit separator next to decimal point"),
+        ("1._5", "digit separator next to decimal point"),
+    ] {
+        let token = Scanner::new(source).scan_token();
+        assert_eq!(token.token_type, TokenType::Error, "{}", source);
+        let message = format!("Invalid number {}: {}", source, reason);
+        assert!(token.lexeme.contains(&message), "got: {}", token.lexeme);
+    }
+}
+
+#[test]
+fn hex_binary_and_octal_literals() {
+    use lox::token::Literal;
+
+    for (source, expected) in [("0x1F", 31.0), ("0xff", 255.0), ("0b1010", 10.0), ("0o17", 15.0), ("0b1111_0000", 240.0)] {
+        let token = Scanner::new(source).scan_token();
+        assert_eq!(token.token_type, TokenType::Number, "{}", source);
+        assert!(matches!(token.literal, Some(Literal::NumberValue(n)) if n == expected), "{}", source);
+    }
+
+    // 普通的 0 后面跟其他字符不受影响
+    let mut scanner = Scanner::new("0.5 0;");
+    assert_token!(scanner, TokenType::Number);
+    assert_token!(scanner, TokenType::Number);
+    assert_token!(scanner, TokenType::Semicolon);
+}
+
+#[test]
+fn malformed_radix_literals_are_errors() {
+    for (source, reason) in [
+        ("0x", "missing digits"),
+        ("0b2", "invalid digit '2' for base 2"),
+        ("0o8", "invalid digit '8' for base 8"),
+        ("0x1G", "invalid digit 'G' for base 16"),
+        ("0x_1", "digit separator after base prefix"),
+        ("0b1__0", "doubled digit separator"),
+        ("0x10000000000000000", "too large"),
+    ] {
+        let token = Scanner::new(source).scan_token();
+        assert_eq!(token.token_type, TokenType::Error, "{}", source);
+        let message = format!("Invalid number {}: {}", source, reason);
+        assert!(token.lexeme.contains(&message), "got: {}", token.lexeme);
+    }
+}
+
+#[test]
+fn string_escapes_for_null_bell_and_unicode() {
+    use lox::token::Literal;
+
+    let token = Scanner::new(r#""smile \u{1F600}\0\a\u{41}""#).scan_token();
+    assert_eq!(token.token_type, TokenType::String);
+    assert!(
+        matches!(&token.literal, Some(Literal::StringValue(s)) if &**s == "smile \u{1F600}\0\x07A"),
+        "got: {:?}",
+        token.literal
+    );
+}
+
+#[test]
+fn invalid_escapes_report_the_first_one_with_its_line() {
+    // 超出 Unicode 范围的码点
+    let token = Scanner::new(r#""\u{110000}""#).scan_token();
+    assert_eq!(token.token_type, TokenType::Error);
+    assert!(token.lexeme.contains("not a Unicode scalar value"), "got: {}", token.lexeme);
+
+    // 代理区码点同样不是合法字符
+    let token = Scanner::new(r#""\u{D800}""#).scan_token();
+    assert!(token.lexeme.contains("not a Unicode scalar value"), "got: {}", token.lexeme);
+
+    let token = Scanner::new(r#""\u1F600""#).scan_token();
+    assert!(token.lexeme.contains("expected '{' after \\u"), "got: {}", token.lexeme);
+
+    // 字符串跨行时报告第一个非法转义所在的行
+    let token = Scanner::new("\"first\nsecond \\q\nthird \\z\"").scan_token();
+    assert_eq!(token.token_type, TokenType::Error);
+    assert_eq!(token.line, 2);
+    assert!(token.lexeme.contains("Invalid escape sequence \\q"), "got: {}", token.lexeme);
+}
+
+#[test]
+fn triple_quoted_raw_strings_are_verbatim() {
+    use lox::token::Literal;
+
+    let source = "\"\"\"C:\\new\\path \"quoted\" \\u{41}\nsecond line\"\"\" x";
+    let mut scanner = Scanner::new(source);
+    let token = scanner.scan_token();
+    assert_eq!(token.token_type, TokenType::String);
+    assert!(
+        matches!(&token.literal, Some(Literal::StringValue(s)) if &**s == "C:\\new\\path \"quoted\" \\u{41}\nsecond line"),
+        "got: {:?}",
+        token.literal
+    );
+    // 原始字符串内的换行计入行号
+    assert_eq!(scanner.scan_token().line, 2);
+
+    // 空字符串后面不是第三个引号时仍是普通字符串
+    let mut scanner = Scanner::new("\"\" + \"\"");
+    assert_token!(scanner, TokenType::String);
+    assert_token!(scanner, TokenType::Plus);
+    assert_token!(scanner, TokenType::String);
+}
+
+#[test]
+fn unterminated_raw_string_is_an_error() {
+    let token = Scanner::new("\"\"\"never\nclosed\"\"").scan_token();
+    assert_eq!(token.token_type, TokenType::Error);
+    assert_eq!(token.line, 1);
+    assert!(token.lexeme.contains("Unterminated raw string"), "got: {}", token.lexeme);
+}
+
+#[test]
+fn leading_decimal_point_starts_a_number() {
+    use lox::token::Literal;
+
+    for (source, expected) in [(".5", 0.5), (".0", 0.0), (".2_5", 0.25)] {
+        let token = Scanner::new(source).scan_token();
+        assert_eq!(token.token_type, TokenType::Number, "{}", source);
+        assert!(matches!(token.literal, Some(Literal::NumberValue(n)) if n == expected), "{}", source);
+    }
+
+    let mut scanner = Scanner::new("123 456.789 .5 -.5");
+    for _ in 0..3 {
+        assert_token!(scanner, TokenType::Number);
+    }
+    assert_token!(scanner, TokenType::Minus);
+    assert_token!(scanner, TokenType::Number);
+}
+
+#[test]
+fn dot_after_an_operand_is_member_access() {
+    let mut scanner = Scanner::new("a.5 f().5 l[0].5");
+    assert_token!(scanner, TokenType::Identifier);
+    assert_token!(scanner, TokenType::Dot);
+    assert_token!(scanner, TokenType::Number);
+    assert_token!(scanner, TokenType::Identifier);
+    assert_token!(scanner, TokenType::LeftParen);
+    assert_token!(scanner, TokenType::RightParen);
+    assert_token!(scanner, TokenType::Dot);
+    assert_token!(scanner, TokenType::Number);
+    assert_token!(scanner, TokenType::Identifier);
+    assert_token!(scanner, TokenType::LeftBracket);
+    assert_token!(scanner, TokenType::Number);
+    assert_token!(scanner, TokenType::RightBracket);
+    assert_token!(scanner, TokenType::Dot);
+    assert_token!(scanner, TokenType::Number);
+}
+
+#[test]
+fn three_dots_scan_as_ellipsis() {
+    let mut scanner = Scanner::new("f(...xs) a..b");
+    assert_token!(scanner, TokenType::Identifier);
+    assert_token!(scanner, TokenType::LeftParen);
+    assert_token!(scanner, TokenType::Ellipsis);
+    assert_token!(scanner, TokenType::Identifier);
+    assert_token!(scanner, TokenType::RightParen);
+    // 两个点仍是两个 Dot
+    assert_token!(scanner, TokenType::Identifier);
+    assert_token!(scanner, TokenType::Dot);
+    assert_token!(scanner, TokenType::Dot);
+    assert_token!(scanner, TokenType::Identifier);
+}
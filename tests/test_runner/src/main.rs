@@ -20,7 +20,7 @@ fn main() {
 
     // 串行执行测试
     let mut passed = 0;
-    for case_id in 1..=35 {
+    for case_id in 1..=113 {
         let (_id, is_pass, msg) = run_single_test(case_id, &cases_path);
         println!("{}", msg);
         if is_pass { passed += 1; }
@@ -29,9 +29,9 @@ fn main() {
     // 最终统计
     println!("\n{} 总用例: {}  通过: {}  失败: {}",
         "结果汇总:".cyan().bold(),
-        35.to_string().yellow(),
+        113.to_string().yellow(),
         passed.to_string().green(),
-        (35-passed).to_string().red()
+        (113-passed).to_string().red()
     );
 }
 
@@ -0,0 +1,116 @@
+use lox::token::{literal_eq, Literal};
+
+fn string(s: &str) -> Literal {
+    Literal::StringValue(s.into())
+}
+
+#[test]
+fn numbers_compare_by_value() {
+    assert!(literal_eq(&Literal::NumberValue(1.5), &Literal::NumberValue(1.5)));
+    assert!(!literal_eq(&Literal::NumberValue(1.0), &Literal::NumberValue(2.0)));
+    // 差值小于 EPSILON 视为相等
+    assert!(literal_eq(
+        &Literal::NumberValue(1.0),
+        &Literal::NumberValue(1.0 + f64::EPSILON / 2.0)
+    ));
+}
+
+#[test]
+fn strings_compare_by_content() {
+    assert!(literal_eq(&string("lox"), &string("lox")));
+    assert!(!literal_eq(&string("lox"), &string("Lox")));
+}
+
+#[test]
+fn nil_only_equals_nil() {
+    assert!(literal_eq(&Literal::Nil, &Literal::Nil));
+    assert!(!literal_eq(&Literal::Nil, &Literal::Boolean(false)));
+}
+
+#[test]
+fn different_types_are_never_equal() {
+    assert!(!literal_eq(&Literal::NumberValue(1.0), &string("1")));
+    assert!(!literal_eq(&Literal::Boolean(true), &Literal::NumberValue(1.0)));
+    assert!(!literal_eq(&string(""), &Literal::Nil));
+}
+
+#[test]
+fn lists_and_maps_compare_by_content() {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    let list = |items: Vec<Literal>| Literal::ListValue(Rc::new(RefCell::new(items)));
+    let map = |entries: &[(&str, f64)]| {
+        let entries = entries.iter().map(|(k, v)| (k.to_string(), Literal::NumberValue(*v))).collect::<HashMap<_, _>>();
+        Literal::MapValue(Rc::new(RefCell::new(entries)))
+    };
+
+    assert!(literal_eq(
+        &list(vec![Literal::NumberValue(1.0), string("a")]),
+        &list(vec![Literal::NumberValue(1.0), string("a")])
+    ));
+    assert!(!literal_eq(&list(vec![Literal::NumberValue(1.0)]), &list(vec![])));
+    assert!(literal_eq(&map(&[("a", 1.0), ("b", 2.0)]), &map(&[("b", 2.0), ("a", 1.0)])));
+    assert!(!literal_eq(&map(&[("a", 1.0)]), &map(&[("a", 2.0)])));
+}
+
+#[test]
+fn as_number_only_matches_numbers() {
+    assert_eq!(Literal::NumberValue(2.5).as_number(), Some(2.5));
+    assert_eq!(string("2.5").as_number(), None);
+    assert_eq!(Literal::Nil.as_number(), None);
+}
+
+#[test]
+fn as_string_only_matches_strings() {
+    assert_eq!(string("lox").as_string(), Some("lox"));
+    assert_eq!(Literal::NumberValue(1.0).as_string(), None);
+    assert_eq!(Literal::Boolean(true).as_string(), None);
+}
+
+#[test]
+fn as_bool_only_matches_booleans() {
+    assert_eq!(Literal::Boolean(false).as_bool(), Some(false));
+    assert_eq!(Literal::Boolean(true).as_bool(), Some(true));
+    // nil 虽然为假，但不是布尔值
+    assert_eq!(Literal::Nil.as_bool(), None);
+    assert_eq!(string("true").as_bool(), None);
+}
+
+#[test]
+fn display_matches_print_output() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    assert_eq!(Literal::Nil.to_string(), "nil");
+    assert_eq!(Literal::Boolean(true).to_string(), "true");
+    assert_eq!(Literal::NumberValue(3.0).to_string(), "3");
+    assert_eq!(Literal::NumberValue(0.5).to_string(), "0.5");
+    assert_eq!(Literal::NumberValue(f64::INFINITY).to_string(), "Infinity");
+    assert_eq!(string("lox").to_string(), "lox");
+    // 集合内的字符串带引号
+    let list = Literal::ListValue(Rc::new(RefCell::new(vec![Literal::NumberValue(1.0), string("a")])));
+    assert_eq!(list.to_string(), "[1, \"a\"]");
+    assert_eq!(format!("value: {}", list), "value: [1, \"a\"]");
+    assert_eq!(lox::interpreter::stringify(&list), list.to_string());
+
+    // 集合内字符串中的引号、反斜杠和控制字符转义，顶层字符串原样输出
+    let tricky = string("say \"hi\"\\\n\t\u{1b}");
+    assert_eq!(tricky.to_string(), "say \"hi\"\\\n\t\u{1b}");
+    let list = Literal::ListValue(Rc::new(RefCell::new(vec![tricky.clone()])));
+    assert_eq!(list.to_string(), r#"["say \"hi\"\\\n\t\u{1b}"]"#);
+    let map = Literal::MapValue(Rc::new(RefCell::new(
+        [("a\"b".to_string(), tricky)].into_iter().collect(),
+    )));
+    assert_eq!(map.to_string(), r#"{"a\"b": "say \"hi\"\\\n\t\u{1b}"}"#);
+}
+
+#[test]
+fn partial_eq_follows_lox_equality() {
+    assert_eq!(Literal::NumberValue(2.0), Literal::NumberValue(2.0));
+    assert_eq!(string("a"), string("a"));
+    assert_ne!(Literal::Nil, Literal::Boolean(false));
+    assert_ne!(Literal::NumberValue(1.0), string("1"));
+    assert_eq!(Literal::NumberValue(1.0) == string("1"), literal_eq(&Literal::NumberValue(1.0), &string("1")));
+}